@@ -2,21 +2,36 @@
 // Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
 // SPDX-License-Identifier: GPL-2.0-or-later
 
+use std::cell::RefCell;
+use std::path::Path;
+use std::path::PathBuf;
+use std::rc::Rc;
+
 use ::gio::prelude::*;
 use ::gtk::prelude::*;
 
+use crate::settings::Settings;
+use crate::simpleaction::SimpleAction;
 use crate::window::Window;
 
 #[derive(Clone)]
 pub struct Application {
     parent: gtk::Application,
+    settings: Settings,
+    windows: Rc<RefCell<Vec<Window>>>,
+    newwindowaction: SimpleAction,
+    quitaction: SimpleAction,
 }
 
 impl Application {
     pub fn new() -> Self {
-        let flags = gio::ApplicationFlags::empty();
+        let flags = gio::ApplicationFlags::HANDLES_OPEN;
         let ret = Self {
             parent: gtk::Application::new(Some("org.kiyuko.Tesserama"), flags),
+            settings: Settings::new(),
+            windows: Rc::new(RefCell::new(Vec::new())),
+            newwindowaction: SimpleAction::new("newwindow"),
+            quitaction: SimpleAction::new("quit"),
         };
         ret.setup();
         ret
@@ -27,11 +42,34 @@ impl Application {
         self.parent.set_accels_for_action("win.insert", &["<Ctrl>i"]);
         self.parent.set_accels_for_action("win.open", &["<Ctrl>o"]);
         self.parent.set_accels_for_action("win.save", &["<Ctrl>s"]);
+        self.parent.set_accels_for_action("win.editpeople", &["<Ctrl>e"]);
+        self.parent.set_accels_for_action("win.undo", &["<Ctrl>z"]);
+        self.parent.set_accels_for_action("win.findreplace", &["<Ctrl>h"]);
+        self.parent.set_accels_for_action("win.printcards", &["<Ctrl>p"]);
+        self.parent.set_accels_for_action("app.newwindow", &["<Ctrl>n"]);
+        self.parent.set_accels_for_action("app.quit", &["<Ctrl>q"]);
+
+        let _self = self.clone();
+        self.newwindowaction.as_parent().connect_activate(move |_,_| {
+            _self.new_window_action_activated();
+        });
+        self.parent.add_action(self.newwindowaction.as_parent());
+
+        let _self = self.clone();
+        self.quitaction.as_parent().connect_activate(move |_,_| {
+            _self.quit_action_activated();
+        });
+        self.parent.add_action(self.quitaction.as_parent());
 
         let _self = self.clone();
         self.parent.connect_activate(move |_| {
             _self.activate_action();
         });
+
+        let _self = self.clone();
+        self.parent.connect_open(move |_, files, _| {
+            _self.open_files(files);
+        });
     }
 
     pub fn run(&self) {
@@ -42,7 +80,124 @@ impl Application {
         gtk::ApplicationWindow::new(&self.parent)
     }
 
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    fn new_window(&self) -> Window {
+        let window = Window::new(self);
+        window.show_all();
+
+        self.windows.borrow_mut().push(window.clone());
+
+        window
+    }
+
+    // Called by windows when they're destroyed
+    pub fn remove_window(&self, window: &gtk::ApplicationWindow) {
+        self.windows.borrow_mut().retain(|w| w.as_parent() != window);
+    }
+
+    // The window the document is open in, if any
+    pub fn find_window(&self, filename: &Path) -> Option<Window> {
+        self.windows.borrow().iter().find(|w| {
+            w.source_filename() == filename
+        }).cloned()
+    }
+
+    // Each document gets its own window: if the document is already
+    // open, the corresponding window is brought to the front instead
+    // of opening it a second time. The window the request comes from
+    // is reused if it doesn't contain a document yet
+    pub fn open_file(&self, filename: PathBuf, uri: String, from: Option<&Window>) {
+        if let Some(window) = self.find_window(&filename) {
+            window.present();
+            return;
+        }
+
+        let window = match from {
+            Some(window) if !window.has_document() => window.clone(),
+            _ => self.new_window(),
+        };
+
+        window.open(filename, uri);
+    }
+
+    // High-level actions
+
     fn activate_action(&self) {
-        Window::new(&self).show_all();
+        self.new_window();
+    }
+
+    fn open_files(&self, files: &[gio::File]) {
+        for file in files {
+            if let Some(filename) = file.path() {
+                self.open_file(filename, file.uri().to_string(), None);
+            }
+        }
+    }
+
+    fn new_window_action(&self) {
+        self.new_window();
+    }
+
+    // Unsaved changes in all open documents are dealt with at once,
+    // rather than asking about each of them separately
+    fn quit_action(&self) {
+        let windows: Vec<Window> = self.windows.borrow().clone();
+        let dirty: Vec<&Window> = windows.iter().filter(|w| w.is_dirty()).collect();
+
+        if !dirty.is_empty() {
+            let names: Vec<String> = dirty.iter().map(|w| w.document_name()).collect();
+
+            let dialog = gtk::MessageDialog::new(
+                self.parent.active_window().as_ref(),
+                gtk::DialogFlags::MODAL,
+                gtk::MessageType::Question,
+                gtk::ButtonsType::None,
+                "Save changes before quitting?",
+            );
+            dialog.set_secondary_text(Some(&format!(
+                "The following documents have unsaved changes:\n\n{}",
+                names.join("\n"),
+            )));
+            dialog.add_button("Cancel", gtk::ResponseType::Cancel);
+            dialog.add_button("Quit without saving", gtk::ResponseType::Reject);
+            dialog.add_button("Save all", gtk::ResponseType::Accept);
+            dialog.set_default_response(gtk::ResponseType::Accept);
+
+            let response = dialog.run();
+
+            unsafe {
+                dialog.destroy();
+            }
+
+            match response {
+                gtk::ResponseType::Accept => {
+                    for window in &dirty {
+                        if !window.save() {
+                            return;
+                        }
+                    }
+                },
+                gtk::ResponseType::Reject => {},
+                _ => return,
+            }
+        }
+
+        for window in &windows {
+            window.close();
+        }
+        self.parent.quit();
+    }
+
+    // Signal handlers
+
+    fn new_window_action_activated(&self) {
+        self.new_window_action();
+    }
+
+    fn quit_action_activated(&self) {
+        self.quit_action();
     }
 }
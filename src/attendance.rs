@@ -0,0 +1,55 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::path::Path;
+
+// The list of members who have checked in at an event, identified
+// by card number, along with the time they arrived at
+pub struct Attendance {
+    event: String,
+    entries: Vec<(String, String)>,
+}
+
+impl Attendance {
+    pub fn new(event: &str, entries: Vec<(String, String)>) -> Self {
+        Self {
+            event: String::from(event),
+            entries,
+        }
+    }
+
+    pub fn event(&self) -> &str {
+        &self.event
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    // Returns the time the member has checked in at, if they have
+    pub fn checked_in(&self, number: &str) -> Option<&str> {
+        self.entries.iter().find(|(n, _)| n == number).map(|(_, time)| time.as_str())
+    }
+
+    // Writes the attendance list in a format suitable for sharing,
+    // with names looked up using the provided function
+    pub fn export<F: Fn(&str) -> String>(&self, path: &Path, people: F) -> csv::Result<()> {
+        let mut writer = csv::Writer::from_path(path)?;
+
+        writer.write_record(&["Number", "People", "Time"])?;
+
+        for (number, time) in &self.entries {
+            writer.write_record(&[number, &people(number), time])?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    // Marks the member as present
+    pub fn check_in(&mut self, number: &str, time: &str) {
+        self.entries.push((String::from(number), String::from(time)));
+    }
+}
@@ -0,0 +1,212 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::iter;
+
+// Cards carry a Code 39 barcode encoding the card number, preceded
+// by a prefix that makes scanned codes easy to tell apart from
+// regular searches and followed by a Luhn check digit, eg.
+//
+//   TS422
+//
+// for card number 42. Only letters and digits are used, because
+// USB scanners pretend to be keyboards and symbols end up being
+// mangled when the keyboard layout is not the one they expect
+const PREFIX: &str = "TS";
+
+// Each character is made of five bars and the four spaces between
+// them, starting with a bar. Three of those elements are wide
+const PATTERNS: &[(char, &str)] = &[
+    ('0', "nnnwwnwnn"),
+    ('1', "wnnwnnnnw"),
+    ('2', "nnwwnnnnw"),
+    ('3', "wnwwnnnnn"),
+    ('4', "nnnwwnnnw"),
+    ('5', "wnnwwnnnn"),
+    ('6', "nnwwwnnnn"),
+    ('7', "nnnwnnwnw"),
+    ('8', "wnnwnnwnn"),
+    ('9', "nnwwnnwnn"),
+    ('S', "nnwnnnwwn"),
+    ('T', "nnnnwnwwn"),
+    ('*', "nwnnwnwnn"),
+];
+
+// Start and stop character
+const DELIMITER: char = '*';
+
+// Ratio between wide and narrow elements
+const WIDE: f64 = 2.5;
+
+// Returns the code for a card number, or None if the number is not
+// something that can be encoded
+pub fn encode(number: &str) -> Option<String> {
+    if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    Some(format!("{}{}{}", PREFIX, number, check_digit(number)))
+}
+
+// The opposite of encode(): returns the card number if the text is
+// a valid code. Lowercase is accepted, since scanners sometimes end
+// up typing it when Caps Lock is on
+pub fn decode(text: &str) -> Option<&str> {
+    if text.len() < PREFIX.len() + 2 || !text.is_char_boundary(PREFIX.len()) {
+        return None;
+    }
+
+    let (prefix, digits) = text.split_at(PREFIX.len());
+
+    if !prefix.eq_ignore_ascii_case(PREFIX) || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let (number, check) = digits.split_at(digits.len() - 1);
+
+    if check.starts_with(check_digit(number)) {
+        Some(number)
+    } else {
+        None
+    }
+}
+
+// Luhn algorithm, as used by credit cards: it catches all single
+// digit mistakes and most transpositions
+fn check_digit(number: &str) -> char {
+    let sum: u32 = number.chars().rev().filter_map(|c| c.to_digit(10)).enumerate().map(|(i, digit)| {
+        if i % 2 == 0 {
+            let doubled = digit * 2;
+            doubled / 10 + doubled % 10
+        } else {
+            digit
+        }
+    }).sum();
+
+    char::from_digit((10 - sum % 10) % 10, 10).unwrap()
+}
+
+fn pattern(c: char) -> Option<&'static str> {
+    PATTERNS.iter().find(|(p, _)| *p == c).map(|(_, pattern)| *pattern)
+}
+
+// Returns the sequence of narrow and wide elements for the code,
+// including the start and stop characters
+fn elements(code: &str) -> String {
+    let mut ret = String::new();
+
+    let chars = iter::once(DELIMITER).chain(code.chars()).chain(iter::once(DELIMITER));
+
+    for c in chars {
+        if let Some(pattern) = pattern(c.to_ascii_uppercase()) {
+            ret.push_str(pattern);
+            // Narrow gap between characters
+            ret.push('n');
+        }
+    }
+    ret.pop();
+
+    ret
+}
+
+// Draws the barcode for the code so that it fills the given area,
+// with the top left corner at the current origin
+pub fn draw(cr: &cairo::Context, code: &str, width: f64, height: f64) {
+    let elements = elements(code);
+
+    let units: f64 = elements.chars().map(|e| if e == 'w' { WIDE } else { 1.0 }).sum();
+    let unit = width / units;
+
+    let _ = cr.save();
+    cr.set_source_rgb(0.0, 0.0, 0.0);
+
+    let mut x = 0.0;
+    for (i, element) in elements.chars().enumerate() {
+        let size = if element == 'w' { WIDE * unit } else { unit };
+
+        // Bars and spaces alternate, starting with a bar
+        if i % 2 == 0 {
+            cr.rectangle(x, 0.0, size, height);
+        }
+        x += size;
+    }
+
+    let _ = cr.fill();
+    let _ = cr.restore();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for number in &["0", "7", "42", "123", "2021", "9876543210"] {
+            let code = encode(number).unwrap();
+
+            assert!(code.starts_with(PREFIX));
+            assert_eq!(decode(&code), Some(*number));
+            assert_eq!(decode(&code.to_lowercase()), Some(*number));
+        }
+    }
+
+    #[test]
+    fn check_digit_values() {
+        assert_eq!(encode("42").unwrap(), "TS422");
+        // The classic Luhn example
+        assert_eq!(encode("7992739871").unwrap(), "TS79927398713");
+    }
+
+    #[test]
+    fn wrong_check_digit() {
+        assert_eq!(decode("TS421"), None);
+        assert_eq!(decode("TS423"), None);
+        // Swapping two adjacent digits is caught as well
+        assert_eq!(decode("TS1230"), Some("123"));
+        assert_eq!(decode("TS2130"), None);
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(encode(""), None);
+        assert_eq!(encode("12a"), None);
+        assert_eq!(encode("-1"), None);
+
+        assert_eq!(decode(""), None);
+        assert_eq!(decode("TS"), None);
+        assert_eq!(decode("TS4"), None);
+        assert_eq!(decode("XX422"), None);
+        assert_eq!(decode("TS4a2"), None);
+        assert_eq!(decode("Tè422"), None);
+        assert_eq!(decode("42"), None);
+    }
+
+    #[test]
+    fn patterns() {
+        let mut seen = Vec::new();
+
+        for (c, pattern) in PATTERNS {
+            assert_eq!(pattern.len(), 9, "{}", c);
+            assert_eq!(pattern.matches('w').count(), 3, "{}", c);
+            assert!(pattern.chars().all(|e| e == 'n' || e == 'w'), "{}", c);
+            assert!(!seen.contains(pattern), "{}", c);
+            seen.push(*pattern);
+        }
+
+        // Everything encode() can produce has a pattern
+        for c in "0123456789".chars().chain(PREFIX.chars()) {
+            assert!(pattern(c).is_some(), "{}", c);
+        }
+    }
+
+    #[test]
+    fn code_elements() {
+        assert_eq!(
+            elements("TS1"),
+            "nwnnwnwnn n nnnnwnwwn n nnwnnnwwn n wnnwnnnnw n nwnnwnwnn".replace(' ', ""),
+        );
+        // Scanners sometimes type lowercase, which is drawn the same
+        assert_eq!(elements("ts1"), elements("TS1"));
+    }
+}
@@ -0,0 +1,204 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use ::gtk::prelude::*;
+
+use crate::column::Column;
+
+// Columns that can be changed in bulk, in the same order they're
+// found in the treeview
+const COLUMNS: [Column; 5] = [
+    Column::Number,
+    Column::People,
+    Column::Signature,
+    Column::ID,
+    Column::Date,
+];
+
+pub enum Change {
+    // Replaces the whole value
+    Set(String),
+    // Replaces all occurrences of the first string with the second
+    Replace(String, String),
+}
+
+impl Change {
+    pub fn apply(&self, value: &str) -> String {
+        match self {
+            Change::Set(text) => text.clone(),
+            Change::Replace(find, replace) => value.replace(find.as_str(), replace),
+        }
+    }
+}
+
+pub struct BulkEdit {
+    pub column: Column,
+    pub change: Change,
+    // Whether the change applies to the selected records rather than
+    // to all those currently displayed
+    pub selected_only: bool,
+}
+
+#[derive(Clone)]
+pub struct BulkEditDialog {
+    parent: gtk::Dialog,
+    column: gtk::ComboBoxText,
+    setbutton: gtk::RadioButton,
+    replacebutton: gtk::RadioButton,
+    value: gtk::Entry,
+    find: gtk::Entry,
+    replace: gtk::Entry,
+    selectedbutton: gtk::RadioButton,
+    shownbutton: gtk::RadioButton,
+}
+
+impl BulkEditDialog {
+    pub fn new<W: IsA<gtk::Window>>(window: &W, selected: usize, shown: usize) -> Self {
+        let setbutton = gtk::RadioButton::with_label("Set to");
+        let selectedbutton = gtk::RadioButton::with_label(&format!("Selected members ({})", selected));
+        let ret = Self {
+            parent: gtk::Dialog::with_buttons(
+                Some("Set field"),
+                Some(window),
+                gtk::DialogFlags::MODAL,
+                &[("Cancel", gtk::ResponseType::Cancel), ("Apply", gtk::ResponseType::Ok)],
+            ),
+            column: gtk::ComboBoxText::new(),
+            replacebutton: gtk::RadioButton::with_label_from_widget(&setbutton, "Replace"),
+            setbutton,
+            value: gtk::Entry::new(),
+            find: gtk::Entry::new(),
+            replace: gtk::Entry::new(),
+            shownbutton: gtk::RadioButton::with_label_from_widget(&selectedbutton, &format!("All members shown ({})", shown)),
+            selectedbutton,
+        };
+        ret.setup(selected, shown);
+        ret
+    }
+
+    fn setup(&self, selected: usize, shown: usize) {
+        self.parent.set_default_response(gtk::ResponseType::Ok);
+        self.parent.set_default_size(400, -1);
+
+        for column in &COLUMNS {
+            self.column.append(Some(column.name()), column.title());
+        }
+        self.column.set_active(Some(0));
+
+        let with = gtk::Label::new(Some("with"));
+        with.style_context().add_class("dim-label");
+
+        for entry in &[&self.value, &self.find, &self.replace] {
+            entry.set_activates_default(true);
+            entry.set_hexpand(true);
+
+            let _self = self.clone();
+            entry.connect_changed(move |_| {
+                _self.entry_changed();
+            });
+        }
+
+        for button in &[&self.setbutton, &self.replacebutton] {
+            let _self = self.clone();
+            button.connect_toggled(move |_| {
+                _self.mode_toggled();
+            });
+        }
+
+        // Acting on the selection only makes sense if there's more
+        // than one selected member, otherwise the displayed ones are
+        // a more likely target
+        self.selectedbutton.set_sensitive(selected > 0);
+        self.shownbutton.set_sensitive(shown > 0);
+        if selected > 1 || shown == 0 {
+            self.selectedbutton.set_active(true);
+        } else {
+            self.shownbutton.set_active(true);
+        }
+
+        let label = |text: &str| {
+            let label = gtk::Label::new(Some(text));
+            label.set_xalign(1.0);
+            label.style_context().add_class("dim-label");
+            label
+        };
+
+        let grid = gtk::Grid::new();
+        grid.set_border_width(12);
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(12);
+
+        grid.attach(&label("Field"), 0, 0, 1, 1);
+        grid.attach(&self.column, 1, 0, 3, 1);
+        grid.attach(&self.setbutton, 0, 1, 1, 1);
+        grid.attach(&self.value, 1, 1, 3, 1);
+        grid.attach(&self.replacebutton, 0, 2, 1, 1);
+        grid.attach(&self.find, 1, 2, 1, 1);
+        grid.attach(&with, 2, 2, 1, 1);
+        grid.attach(&self.replace, 3, 2, 1, 1);
+        grid.attach(&label("Apply to"), 0, 3, 1, 1);
+        grid.attach(&self.selectedbutton, 1, 3, 3, 1);
+        grid.attach(&self.shownbutton, 1, 4, 3, 1);
+
+        self.parent.content_area().add(&grid);
+
+        self.update();
+    }
+
+    fn update(&self) {
+        let replacing = self.replacebutton.is_active();
+
+        self.value.set_sensitive(!replacing);
+        self.find.set_sensitive(replacing);
+        self.replace.set_sensitive(replacing);
+
+        // Replacing the empty string would insert the replacement
+        // between every character
+        let valid = !replacing || !self.find.text().is_empty();
+        self.parent.set_response_sensitive(gtk::ResponseType::Ok, valid);
+    }
+
+    fn bulk_edit(&self) -> Option<BulkEdit> {
+        let column = self.column.active_id().and_then(|id| Column::from_name(&id))?;
+
+        let change = if self.replacebutton.is_active() {
+            Change::Replace(self.find.text().to_string(), self.replace.text().to_string())
+        } else {
+            Change::Set(self.value.text().trim().to_string())
+        };
+
+        Some(BulkEdit {
+            column,
+            change,
+            selected_only: self.selectedbutton.is_active(),
+        })
+    }
+
+    // Returns the change to apply, or None if the user has cancelled
+    // the operation
+    pub fn run(&self) -> Option<BulkEdit> {
+        self.parent.show_all();
+
+        let ret = match self.parent.run() {
+            gtk::ResponseType::Ok => self.bulk_edit(),
+            _ => None,
+        };
+
+        unsafe {
+            self.parent.destroy();
+        }
+
+        ret
+    }
+
+    // Signal handlers
+
+    fn entry_changed(&self) {
+        self.update();
+    }
+
+    fn mode_toggled(&self) {
+        self.update();
+    }
+}
@@ -0,0 +1,164 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::path::Path;
+use std::rc::Rc;
+
+use ::gtk::prelude::*;
+
+use crate::cardtemplate;
+use crate::cardtemplate::CardTemplate;
+use crate::cardtemplate::Sheet;
+use crate::column::Column;
+
+// Sizes in millimeters
+const A4_WIDTH: f64 = 210.0;
+const A4_HEIGHT: f64 = 297.0;
+const MARGIN: f64 = 10.0;
+const GAP: f64 = 8.0;
+const CROP_MARK: f64 = 3.0;
+
+#[derive(Clone)]
+pub struct CardPrinter {
+    template: Rc<CardTemplate>,
+    records: Rc<Vec<[String; Column::SIZE]>>,
+}
+
+impl CardPrinter {
+    pub fn new(template: CardTemplate, records: Vec<[String; Column::SIZE]>) -> Self {
+        Self {
+            template: Rc::new(template),
+            records: Rc::new(records),
+        }
+    }
+
+    // Page size in millimeters
+    fn page_size(&self) -> (f64, f64) {
+        match self.template.sheet {
+            Sheet::A4 => (A4_WIDTH, A4_HEIGHT),
+            Sheet::Card => (self.template.width, self.template.height),
+        }
+    }
+
+    // Returns the number of columns and rows of cards that fit on a
+    // page, as well as the position of the top left one
+    fn grid(&self) -> (usize, usize, f64, f64) {
+        let (page_width, page_height) = self.page_size();
+        let (width, height) = (self.template.width, self.template.height);
+
+        if self.template.sheet == Sheet::Card {
+            return (1, 1, 0.0, 0.0);
+        }
+
+        let fit = |available: f64, size: f64| {
+            (((available - 2.0 * MARGIN + GAP) / (size + GAP)).floor() as usize).max(1)
+        };
+        let columns = fit(page_width, width);
+        let rows = fit(page_height, height);
+
+        // Center the grid on the page
+        let used_width = columns as f64 * (width + GAP) - GAP;
+        let used_height = rows as f64 * (height + GAP) - GAP;
+
+        (columns, rows, (page_width - used_width) / 2.0, (page_height - used_height) / 2.0)
+    }
+
+    pub fn n_pages(&self) -> i32 {
+        let (columns, rows, _, _) = self.grid();
+        let per_page = columns * rows;
+
+        ((self.records.len() + per_page - 1) / per_page) as i32
+    }
+
+    pub fn draw_page(&self, cr: &cairo::Context, page: i32) {
+        let (columns, rows, left, top) = self.grid();
+        let per_page = columns * rows;
+        let first = page as usize * per_page;
+
+        let records = self.records.iter().skip(first).take(per_page);
+
+        for (i, record) in records.enumerate() {
+            let x = left + (i % columns) as f64 * (self.template.width + GAP);
+            let y = top + (i / columns) as f64 * (self.template.height + GAP);
+
+            if self.template.sheet == Sheet::A4 {
+                self.draw_crop_marks(cr, x, y);
+            }
+
+            let _ = cr.save();
+            cr.translate(cardtemplate::mm(x), cardtemplate::mm(y));
+            self.template.draw(cr, record);
+            let _ = cr.restore();
+        }
+    }
+
+    // Crop marks are drawn just outside each corner of the card, so
+    // that they don't end up on the card itself after cutting
+    fn draw_crop_marks(&self, cr: &cairo::Context, x: f64, y: f64) {
+        let mm = cardtemplate::mm;
+        let (width, height) = (self.template.width, self.template.height);
+        let offset = 1.0;
+
+        let _ = cr.save();
+        cr.set_source_rgb(0.0, 0.0, 0.0);
+        cr.set_line_width(0.25);
+
+        for (cx, dx) in &[(x, -1.0), (x + width, 1.0)] {
+            for (cy, dy) in &[(y, -1.0), (y + height, 1.0)] {
+                // Horizontal mark
+                cr.move_to(mm(cx + dx * offset), mm(*cy));
+                cr.line_to(mm(cx + dx * (offset + CROP_MARK)), mm(*cy));
+                // Vertical mark
+                cr.move_to(mm(*cx), mm(cy + dy * offset));
+                cr.line_to(mm(*cx), mm(cy + dy * (offset + CROP_MARK)));
+            }
+        }
+
+        let _ = cr.stroke();
+        let _ = cr.restore();
+    }
+
+    pub fn print<W: IsA<gtk::Window>>(&self, window: &W) -> Result<(), glib::Error> {
+        let (width, height) = self.page_size();
+
+        let paper_size = match self.template.sheet {
+            Sheet::A4 => gtk::PaperSize::new(Some("iso_a4")),
+            Sheet::Card => gtk::PaperSize::new_custom("tesserama-card", "Card", width, height, gtk::Unit::Mm),
+        };
+        let page_setup = gtk::PageSetup::new();
+        page_setup.set_paper_size(&paper_size);
+
+        let operation = gtk::PrintOperation::new();
+        operation.set_job_name("Membership cards");
+        operation.set_default_page_setup(Some(&page_setup));
+        operation.set_use_full_page(true);
+        operation.set_unit(gtk::Unit::Points);
+        operation.set_n_pages(self.n_pages());
+
+        let _self = self.clone();
+        operation.connect_draw_page(move |_, context, page| {
+            if let Some(cr) = context.cairo_context() {
+                _self.draw_page(&cr, page);
+            }
+        });
+
+        operation.run(gtk::PrintOperationAction::PrintDialog, Some(window)).map(|_| ())
+    }
+
+    pub fn export_pdf(&self, path: &Path) -> Result<(), cairo::Error> {
+        let (width, height) = self.page_size();
+
+        let surface = cairo::PdfSurface::new(cardtemplate::mm(width), cardtemplate::mm(height), path)?;
+        let cr = cairo::Context::new(&surface)?;
+
+        for page in 0..self.n_pages() {
+            self.draw_page(&cr, page);
+            cr.show_page()?;
+        }
+
+        surface.finish();
+
+        Ok(())
+    }
+}
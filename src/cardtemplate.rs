@@ -0,0 +1,404 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::fs::File;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::barcode;
+use crate::column::Column;
+
+// Used when the user hasn't provided a template of their own. The
+// logo is loaded from the same directory as the template and simply
+// skipped if it's not there
+const DEFAULT_TEMPLATE: &str = "\
+[Card]
+width=85.6
+height=54
+sheet=a4
+
+[Image logo]
+file=logo.png
+x=62
+y=5
+width=18
+height=18
+
+[Text number]
+x=6
+y=6
+font=Sans Bold 16
+text=N. {number}
+
+[Text people]
+x=6
+y=24
+width=74
+font=Sans 11
+text={people}
+
+[Barcode number]
+x=50
+y=38
+width=32
+height=10
+
+[Text id]
+x=6
+y=38
+width=42
+font=Monospace 8
+text={id}
+
+[Text date]
+x=6
+y=46
+font=Sans 8
+text=Issued on {date}
+";
+
+// Receipts use the same format as cards. Besides record values,
+// they have access to the details of the payment and of the
+// association issuing them
+const DEFAULT_RECEIPT_TEMPLATE: &str = "\
+[Card]
+width=148
+height=105
+sheet=card
+
+[Image logo]
+file=logo.png
+x=8
+y=8
+width=20
+height=20
+
+[Text association]
+x=32
+y=8
+width=108
+font=Sans Bold 12
+text={association}
+
+[Text address]
+x=32
+y=15
+width=108
+font=Sans 8
+text={association-address}
+
+[Text fiscal-code]
+x=32
+y=20
+width=108
+font=Sans 8
+text=Fiscal code {association-fiscal-code}
+
+[Text receipt]
+x=8
+y=34
+font=Sans Bold 14
+text=Receipt N. {receipt}
+
+[Text payment-date]
+x=100
+y=36
+font=Sans 9
+text=Date {payment-date}
+
+[Text people]
+x=8
+y=50
+width=132
+font=Sans 10
+text=Received from {people}
+
+[Text id]
+x=8
+y=57
+width=132
+font=Sans 10
+text=ID {id}
+
+[Text amount]
+x=8
+y=67
+width=132
+font=Sans 10
+text=The sum of € {amount} ({method}) as membership fee for card N. {number}
+
+[Text signature]
+x=90
+y=90
+font=Sans 8
+text=Signature ________________________
+";
+
+// Templates use millimeters, while drawing happens in points
+pub fn mm(value: f64) -> f64 {
+    value * 72.0 / 25.4
+}
+
+// Resolution images are assumed to have when the template doesn't
+// say how big they should be, which is what's commonly used for print
+const IMAGE_DPI: f64 = 300.0;
+
+// Returns the size of an image in millimeters. If only one of the
+// dimensions is provided, the other one is picked so that the image
+// keeps its aspect ratio
+fn image_size(width: Option<f64>, height: Option<f64>, pixel_width: i32, pixel_height: i32) -> (f64, f64) {
+    let natural_width = pixel_width as f64 * 25.4 / IMAGE_DPI;
+    let natural_height = pixel_height as f64 * 25.4 / IMAGE_DPI;
+
+    match (width, height) {
+        (Some(width), Some(height)) => (width, height),
+        (Some(width), None) => (width, width * natural_height / natural_width),
+        (None, Some(height)) => (height * natural_width / natural_height, height),
+        (None, None) => (natural_width, natural_height),
+    }
+}
+
+// Creates a layout for drawing text in points, rather than using
+// the 96 DPI resolution Pango assumes by default
+pub fn create_layout(cr: &cairo::Context, font: &str) -> Option<pango::Layout> {
+    let layout = pangocairo::create_layout(cr)?;
+
+    if let Some(context) = layout.context() {
+        pangocairo::context_set_resolution(&context, 72.0);
+        layout.context_changed();
+    }
+
+    let font = pango::FontDescription::from_string(font);
+    layout.set_font_description(Some(&font));
+
+    Some(layout)
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Sheet {
+    // Several cards on an A4 page, with crop marks
+    A4,
+    // A page the size of a card, for card printers
+    Card,
+}
+
+#[derive(Clone)]
+pub enum Element {
+    Text {
+        x: f64,
+        y: f64,
+        width: Option<f64>,
+        font: String,
+        text: String,
+    },
+    Image {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        surface: cairo::ImageSurface,
+    },
+    Barcode {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+}
+
+// A card template is a key file with a [Card] group describing the
+// size of the card in millimeters, followed by any number of
+// [Text name] and [Image name] groups describing the elements to be
+// drawn on it. In text, {column} is replaced with the value of the
+// corresponding column in the record, eg. {number} or {people}.
+// [Barcode name] groups describe where the card number should be
+// printed in a format that can be scanned
+#[derive(Clone)]
+pub struct CardTemplate {
+    pub width: f64,
+    pub height: f64,
+    pub sheet: Sheet,
+    elements: Vec<Element>,
+}
+
+impl CardTemplate {
+    fn user_path(name: &str) -> PathBuf {
+        let mut path = glib::user_config_dir();
+        path.push("tesserama");
+        path.push(name);
+        path
+    }
+
+    // Loads the user's template, falling back to the default one if
+    // it's missing or invalid
+    fn load_file(name: &str, default: &str) -> Self {
+        let path = CardTemplate::user_path(name);
+        let keyfile = glib::KeyFile::new();
+
+        if keyfile.load_from_file(&path, glib::KeyFileFlags::NONE).is_err() {
+            keyfile.load_from_data(default, glib::KeyFileFlags::NONE)
+                   .expect("Failed to parse default template");
+        }
+
+        CardTemplate::from_keyfile(&keyfile, path.parent().unwrap())
+    }
+
+    pub fn load() -> Self {
+        CardTemplate::load_file("card.ini", DEFAULT_TEMPLATE)
+    }
+
+    pub fn load_receipt() -> Self {
+        CardTemplate::load_file("receipt.ini", DEFAULT_RECEIPT_TEMPLATE)
+    }
+
+    fn from_keyfile(keyfile: &glib::KeyFile, dir: &Path) -> Self {
+        let number = |group: &str, key: &str, default: f64| {
+            keyfile.double(group, key).unwrap_or(default)
+        };
+
+        let sheet = match keyfile.string("Card", "sheet").as_deref() {
+            Ok("card") => Sheet::Card,
+            _ => Sheet::A4,
+        };
+
+        let mut ret = Self {
+            width: number("Card", "width", 85.6),
+            height: number("Card", "height", 54.0),
+            sheet,
+            elements: Vec::new(),
+        };
+
+        let (groups, _) = keyfile.groups();
+
+        for group in groups.iter().map(|g| g.as_str()) {
+            if group.starts_with("Text ") {
+                ret.elements.push(Element::Text {
+                    x: number(group, "x", 0.0),
+                    y: number(group, "y", 0.0),
+                    width: keyfile.double(group, "width").ok(),
+                    font: keyfile.string(group, "font").map_or(String::from("Sans 10"), |f| f.to_string()),
+                    text: keyfile.string(group, "text").map_or(String::new(), |t| t.to_string()),
+                });
+            } else if group.starts_with("Image ") {
+                let file = keyfile.string(group, "file").map(|f| dir.join(f.as_str()));
+                let surface = file.ok().and_then(|file| {
+                    let mut file = File::open(file).ok()?;
+                    cairo::ImageSurface::create_from_png(&mut file).ok()
+                });
+
+                // Missing images are not considered fatal
+                if let Some(surface) = surface {
+                    let (width, height) = image_size(
+                        keyfile.double(group, "width").ok(),
+                        keyfile.double(group, "height").ok(),
+                        surface.width(),
+                        surface.height(),
+                    );
+
+                    ret.elements.push(Element::Image {
+                        x: number(group, "x", 0.0),
+                        y: number(group, "y", 0.0),
+                        width,
+                        height,
+                        surface,
+                    });
+                }
+            } else if group.starts_with("Barcode ") {
+                ret.elements.push(Element::Barcode {
+                    x: number(group, "x", 0.0),
+                    y: number(group, "y", 0.0),
+                    width: number(group, "width", 30.0),
+                    height: number(group, "height", 10.0),
+                });
+            }
+        }
+
+        ret
+    }
+
+    // Placeholders are replaced in a single pass, so that values which
+    // happen to contain something looking like a placeholder end up on
+    // the card as they are. Unknown placeholders are left alone
+    fn expand(text: &str, values: &[String], extra: &[(String, String)]) -> String {
+        let mut ret = String::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find('{') {
+            ret.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let found = rest.find('}').and_then(|end| {
+                let name = &rest[1..end];
+
+                Column::from_name(name).and_then(|column| {
+                    values.get(usize::from(column))
+                }).or_else(|| {
+                    extra.iter().find(|(n, _)| n == name).map(|(_, value)| value)
+                }).map(|value| (value, end))
+            });
+
+            match found {
+                Some((value, end)) => {
+                    ret.push_str(value);
+                    rest = &rest[end + 1..];
+                },
+                None => {
+                    ret.push('{');
+                    rest = &rest[1..];
+                },
+            }
+        }
+        ret.push_str(rest);
+
+        ret
+    }
+
+    // Draws a card for the record, with the top left corner at the
+    // current origin. Units are expected to be points
+    pub fn draw(&self, cr: &cairo::Context, values: &[String]) {
+        self.draw_with(cr, values, &[]);
+    }
+
+    // Same as draw(), with additional placeholders available
+    pub fn draw_with(&self, cr: &cairo::Context, values: &[String], extra: &[(String, String)]) {
+        for element in &self.elements {
+            let _ = cr.save();
+
+            match element {
+                Element::Text { x, y, width, font, text } => {
+                    if let Some(layout) = create_layout(cr, font) {
+                        if let Some(width) = width {
+                            layout.set_width((mm(*width) * pango::SCALE as f64) as i32);
+                            layout.set_ellipsize(pango::EllipsizeMode::End);
+                        }
+                        layout.set_text(&CardTemplate::expand(text, values, extra));
+
+                        cr.set_source_rgb(0.0, 0.0, 0.0);
+                        cr.move_to(mm(*x), mm(*y));
+                        pangocairo::show_layout(cr, &layout);
+                    }
+                },
+                Element::Image { x, y, width, height, surface } => {
+                    cr.translate(mm(*x), mm(*y));
+                    cr.scale(
+                        mm(*width) / surface.width() as f64,
+                        mm(*height) / surface.height() as f64,
+                    );
+                    let _ = cr.set_source_surface(surface, 0.0, 0.0);
+                    let _ = cr.paint();
+                },
+                Element::Barcode { x, y, width, height } => {
+                    // Cards without a valid number simply don't get
+                    // a barcode
+                    if let Some(code) = barcode::encode(&values[usize::from(Column::Number)]) {
+                        cr.translate(mm(*x), mm(*y));
+                        barcode::draw(cr, &code, mm(*width), mm(*height));
+                    }
+                },
+            }
+
+            let _ = cr.restore();
+        }
+    }
+}
@@ -0,0 +1,131 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::rc::Rc;
+
+use ::gtk::prelude::*;
+
+use crate::column;
+use crate::ledger;
+use crate::ledger::Payment;
+
+// Lists the payments collected on a given day, with totals for each
+// payment method, so that the desk can be closed at the end of the
+// day by comparing them against the cash actually in the drawer
+#[derive(Clone)]
+pub struct CashSummaryDialog {
+    parent: gtk::Dialog,
+    calendar: gtk::Calendar,
+    summary: gtk::Label,
+    payments: Rc<Vec<Payment>>,
+}
+
+impl CashSummaryDialog {
+    pub fn new<W: IsA<gtk::Window>>(window: &W, payments: Vec<Payment>) -> Self {
+        let ret = Self {
+            parent: gtk::Dialog::with_buttons(
+                Some("Cash summary"),
+                Some(window),
+                gtk::DialogFlags::MODAL,
+                &[("Close", gtk::ResponseType::Close)],
+            ),
+            calendar: gtk::Calendar::new(),
+            summary: gtk::Label::new(None),
+            payments: Rc::new(payments),
+        };
+        ret.setup();
+        ret
+    }
+
+    fn setup(&self) {
+        self.summary.set_xalign(0.0);
+        self.summary.set_yalign(0.0);
+        self.summary.set_selectable(true);
+        self.summary.set_size_request(300, -1);
+
+        let _self = self.clone();
+        self.calendar.connect_day_selected(move |_| {
+            _self.day_selected();
+        });
+
+        let contents = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        contents.set_border_width(12);
+        contents.pack_start(&self.calendar, false, false, 0);
+        contents.pack_start(&self.summary, true, true, 0);
+        self.parent.content_area().add(&contents);
+
+        self.update_summary();
+    }
+
+    fn selected_date(&self) -> Option<chrono::NaiveDate> {
+        let (year, month, day) = self.calendar.date();
+
+        // Months are zero-based in GtkCalendar
+        chrono::NaiveDate::from_ymd_opt(year as i32, month + 1, day)
+    }
+
+    fn update_summary(&self) {
+        let date = match self.selected_date() {
+            Some(date) => date,
+            None => return,
+        };
+
+        let payments: Vec<&Payment> = self.payments.iter().filter(|p| {
+            column::parse_date(&p.date) == Some(date)
+        }).collect();
+
+        let mut text = format!("<b>{}</b>\n\n", date.format("%d/%m/%Y"));
+
+        if payments.is_empty() {
+            text.push_str("No payments collected.");
+            self.summary.set_markup(&text);
+            return;
+        }
+
+        for payment in &payments {
+            text.push_str(&glib::markup_escape_text(&format!(
+                "N. {}  {}  {}  {}\n",
+                payment.number,
+                ledger::format_amount(payment.amount),
+                payment.method,
+                payment.operator,
+            )));
+        }
+        text.push('\n');
+
+        // Totals are grouped by method, in the order methods are
+        // first encountered
+        let mut methods: Vec<&str> = Vec::new();
+        for payment in &payments {
+            if !methods.contains(&payment.method.as_str()) {
+                methods.push(&payment.method);
+            }
+        }
+
+        for method in methods {
+            let total: i64 = payments.iter().filter(|p| p.method == method).map(|p| p.amount).sum();
+            text.push_str(&glib::markup_escape_text(&format!("{}: {}\n", method, ledger::format_amount(total))));
+        }
+
+        let total: i64 = payments.iter().map(|p| p.amount).sum();
+        text.push_str(&format!("<b>Total: {} ({} payments)</b>", ledger::format_amount(total), payments.len()));
+
+        self.summary.set_markup(&text);
+    }
+
+    pub fn run(&self) {
+        self.parent.show_all();
+        self.parent.run();
+
+        unsafe {
+            self.parent.destroy();
+        }
+    }
+
+    // Signal handlers
+
+    fn day_selected(&self) {
+        self.update_summary();
+    }
+}
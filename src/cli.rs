@@ -0,0 +1,110 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::path::Path;
+
+use crate::column::Column;
+use crate::needle::Needle;
+use crate::report::Report;
+use crate::settings::Settings;
+use crate::storage;
+
+const USAGE: &str = "\
+Usage: tesserama --export-report [--filter QUERY] [--fuzzy] [--sort COLUMN[:desc]] INPUT OUTPUT";
+
+// Handles the operations that can be performed without a graphical
+// interface. Returns None if the arguments don't ask for one of
+// them, in which case the application should be started normally,
+// or the exit status otherwise
+pub fn run(args: &[String]) -> Option<i32> {
+    if args.get(1).map(|arg| arg.as_str()) != Some("--export-report") {
+        return None;
+    }
+
+    let mut query = String::new();
+    let mut fuzzy = false;
+    let mut sort: Option<(Column, bool)> = None;
+    let mut files = Vec::new();
+
+    let mut args = args.iter().skip(2);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--filter" => match args.next() {
+                Some(value) => query = value.clone(),
+                None => return usage(),
+            },
+            "--fuzzy" => fuzzy = true,
+            "--sort" => match args.next().map(String::as_str).and_then(parse_sort) {
+                Some(value) => sort = Some(value),
+                None => return usage(),
+            },
+            _ if arg.starts_with('-') => {
+                eprintln!("Unknown option: {}", arg);
+                return usage();
+            },
+            _ => files.push(arg),
+        }
+    }
+
+    if files.len() != 2 {
+        return usage();
+    }
+
+    Some(export_report(Path::new(files[0]), Path::new(files[1]), &query, fuzzy, sort))
+}
+
+fn usage() -> Option<i32> {
+    eprintln!("{}", USAGE);
+    Some(2)
+}
+
+// Sort orders are specified as the name of a column, optionally
+// followed by :desc, eg. "date:desc"
+fn parse_sort(value: &str) -> Option<(Column, bool)> {
+    let (name, descending) = match value.strip_suffix(":desc") {
+        Some(name) => (name, true),
+        None => (value.strip_suffix(":asc").unwrap_or(value), false),
+    };
+
+    Column::from_name(name).map(|column| (column, descending))
+}
+
+// Produces the same report the graphical interface would, with the
+// filter and sort order being applied the same way
+fn export_report(input: &Path, output: &Path, query: &str, fuzzy: bool, sort: Option<(Column, bool)>) -> i32 {
+    let records = match storage::open(input).load() {
+        Ok(records) => records,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", input.display(), e);
+            return 1;
+        },
+    };
+
+    let needle = Needle::new(query, fuzzy);
+
+    let mut records: Vec<(usize, [String; Column::SIZE])> = records.into_iter().filter_map(|record| {
+        needle.score_record(&record).map(|score| (score, record))
+    }).collect();
+
+    // Without an explicit sort order, the best matches come first
+    if needle.is_fuzzy() {
+        records.sort_by_key(|(score, _)| *score);
+    }
+
+    let mut records: Vec<[String; Column::SIZE]> = records.into_iter().map(|(_, record)| record).collect();
+
+    if let Some((column, descending)) = sort {
+        column.sort(&mut records, descending);
+    }
+
+    let report = Report::new(&Settings::new().association_name(), records);
+
+    if let Err(e) = report.export_pdf(output) {
+        eprintln!("Failed to write {}: {}", output.display(), e);
+        return 1;
+    }
+
+    0
+}
@@ -0,0 +1,168 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use crate::column::Column;
+use crate::csvfile;
+use crate::liststore::ListStore;
+
+// Records are exchanged with spreadsheets and email clients as plain
+// text, with values in the same order as CSV files. The header, if
+// requested, contains the title of each column
+pub fn format(records: &[[String; Column::SIZE]], delimiter: u8, header: bool) -> String {
+    let mut writer = csv::WriterBuilder::new()
+                     .has_headers(false)
+                     .delimiter(delimiter)
+                     .from_writer(Vec::new());
+
+    if header {
+        let titles: Vec<&str> = (0..Column::SIZE).map(|i| Column::from(i).title()).collect();
+        writer.write_record(&titles).expect("Failed to format records");
+    }
+
+    for record in records {
+        writer.write_record(record).expect("Failed to format records");
+    }
+
+    let bytes = writer.into_inner().expect("Failed to format records");
+
+    String::from_utf8(bytes).unwrap_or_default()
+}
+
+// Text copied from a spreadsheet is tab separated, anything else is
+// assumed to be CSV: whichever separator is more common in the first
+// line wins, since values might contain the other one. If the first
+// line is made of column titles, as is the case when copying with
+// the header, it decides which value goes where; otherwise values
+// are mapped the same way as when reading a CSV file. A single
+// column, such as a list of names with one per line, contains the
+// people. Blank lines are skipped
+pub fn parse(text: &str) -> Vec<[String; Column::SIZE]> {
+    let line = text.lines().next().unwrap_or_default();
+    let tabs = line.matches('\t').count();
+    let commas = line.matches(',').count();
+    // With neither, splitting on tabs keeps any comma in the values
+    let delimiter = if tabs >= commas { b'\t' } else { b',' };
+
+    let mut records = read(text, delimiter);
+    let mapping = records.first().and_then(header_mapping);
+
+    if mapping.is_some() {
+        records.remove(0);
+    } else if delimiter == b',' && records.iter().any(|record| record.len() < 3) {
+        // Commas are also used to separate people in the same family,
+        // as in "Rossi Mario, Anna", so unless all lines have at least
+        // the date, the number and the people it's a list of names
+        records = read(text, b'\t');
+    }
+
+    // The card number would be replaced when the records are pasted,
+    // so it can't be what a single column of values stands for
+    let single = records.iter().all(|record| record.len() == 1);
+
+    records.into_iter().map(|record| {
+        match &mapping {
+            Some(mapping) => {
+                let mut values = ListStore::new_row();
+
+                for (column, field) in mapping.iter().zip(record.iter()) {
+                    if let Some(column) = column {
+                        values[usize::from(column.clone())] = String::from(field);
+                    }
+                }
+
+                values
+            },
+            None if single => {
+                let mut values = ListStore::new_row();
+                values[usize::from(Column::People)] = String::from(&record[0]);
+                values
+            },
+            None => csvfile::record_from_fields(record.iter()),
+        }
+    }).collect()
+}
+
+fn read(text: &str, delimiter: u8) -> Vec<csv::StringRecord> {
+    let mut reader = csv::ReaderBuilder::new()
+                     .has_headers(false)
+                     .flexible(true)
+                     .delimiter(delimiter)
+                     .from_reader(text.as_bytes());
+
+    reader.records().flatten().filter(|record| {
+        record.iter().any(|field| !field.trim().is_empty())
+    }).collect()
+}
+
+// Returns the column each field corresponds to, or None if the record
+// doesn't look like a header. Both names and titles are recognized,
+// and empty fields are ignored
+fn header_mapping(record: &csv::StringRecord) -> Option<Vec<Option<Column>>> {
+    let mapping: Vec<Option<Column>> = record.iter().map(|field| {
+        Column::from_name(&field.trim().to_lowercase())
+    }).collect();
+
+    let unknown = record.iter().zip(mapping.iter()).any(|(field, column)| {
+        column.is_none() && !field.trim().is_empty()
+    });
+
+    if unknown {
+        None
+    } else {
+        Some(mapping)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn people(records: &[[String; Column::SIZE]]) -> Vec<&str> {
+        records.iter().map(|record| record[usize::from(Column::People)].as_str()).collect()
+    }
+
+    #[test]
+    fn tab_separated() {
+        let records = parse("01/02/21\t3\tRossi Mario, Anna\n");
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0][usize::from(Column::Date)], "01/02/21");
+        assert_eq!(records[0][usize::from(Column::Number)], "3");
+        assert_eq!(people(&records), vec!["Rossi Mario, Anna"]);
+    }
+
+    #[test]
+    fn comma_separated() {
+        let records = parse("01/02/21,3,\"Rossi Mario, Anna\"\n02/02/21,4,Bianchi Luca\n");
+
+        assert_eq!(records[1][usize::from(Column::Number)], "4");
+        assert_eq!(people(&records), vec!["Rossi Mario, Anna", "Bianchi Luca"]);
+    }
+
+    #[test]
+    fn header() {
+        let records = parse("People\tNumber\nRossi Mario\t3\n");
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0][usize::from(Column::Number)], "3");
+        assert_eq!(people(&records), vec!["Rossi Mario"]);
+    }
+
+    #[test]
+    fn list_of_names() {
+        let records = parse("Rossi Mario\n\nBianchi Luca\n");
+
+        assert_eq!(people(&records), vec!["Rossi Mario", "Bianchi Luca"]);
+        assert!(records.iter().all(|record| record[usize::from(Column::Number)].is_empty()));
+    }
+
+    #[test]
+    fn list_of_families() {
+        assert_eq!(people(&parse("Rossi Mario, Anna\n")), vec!["Rossi Mario, Anna"]);
+        assert_eq!(
+            people(&parse("Rossi Mario, Anna\nBianchi Luca\nVerdi Paolo, Giulia, Marco\n")),
+            vec!["Rossi Mario, Anna", "Bianchi Luca", "Verdi Paolo, Giulia, Marco"],
+        );
+    }
+}
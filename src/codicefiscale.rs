@@ -0,0 +1,257 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use crate::column::Column;
+use crate::needle;
+use crate::person::Person;
+use crate::validator::Validator;
+
+const LENGTH: usize = 16;
+
+// Letters used to encode the month of birth
+const MONTHS: &str = "ABCDEHLMPRST";
+
+// When two people would end up with the same code, some of its digits
+// are replaced by letters, starting from the rightmost one
+const OMOCODIA: &str = "LMNPQRSTUV";
+const OMOCODIA_POSITIONS: [usize; 7] = [6, 7, 9, 10, 12, 13, 14];
+
+// Values used for characters in odd positions, counting from one,
+// when computing the check character. Digits have the same values
+// as the corresponding letters
+const ODD_VALUES: [u32; 26] = [
+    1, 0, 5, 7, 9, 13, 15, 17, 19, 21, 2, 4, 18,
+    20, 11, 3, 6, 8, 12, 14, 16, 10, 22, 25, 24, 23,
+];
+
+// Italian tax code, made of
+//
+//   SSSNNNYYMDDCCCCK
+//
+// where S and N are derived from the surname and given names, Y, M
+// and D encode the date of birth, with 40 added to the day for women,
+// C is the place of birth and K a check character
+pub struct CodiceFiscale {
+    // Whether the code should also match one of the people in the
+    // record. Off by default, since nicknames and typos in the name
+    // are fairly common
+    cross_check: bool,
+}
+
+impl CodiceFiscale {
+    pub fn new(cross_check: bool) -> Self {
+        Self {
+            cross_check,
+        }
+    }
+}
+
+impl Validator for CodiceFiscale {
+    fn validate(&self, value: &str, record: &[String; Column::SIZE]) -> Result<(), String> {
+        let code: Vec<char> = value.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_uppercase).collect();
+
+        check_format(&code)?;
+        check_birth_date(&code)?;
+
+        let expected = check_character(&code[..LENGTH - 1]);
+        if code[LENGTH - 1] != expected {
+            return Err(format!("Wrong check character in codice fiscale, should be {}", expected));
+        }
+
+        if self.cross_check {
+            let surname: String = code[0..3].iter().collect();
+            let given_names: String = code[3..6].iter().collect();
+
+            let matches = Person::parse_list(&record[usize::from(Column::People)]).iter().any(|person| {
+                surname_code(&person.surname) == surname && given_names_code(&person.given_names) == given_names
+            });
+
+            if !matches {
+                return Err(String::from("Codice fiscale doesn't match any of the people"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn check_format(code: &[char]) -> Result<(), String> {
+    let error = || Err(String::from("Not a valid codice fiscale"));
+
+    if code.len() != LENGTH {
+        return error();
+    }
+
+    for (i, c) in code.iter().enumerate() {
+        let ok = if OMOCODIA_POSITIONS.contains(&i) {
+            c.is_ascii_digit() || OMOCODIA.contains(*c)
+        } else if i == 8 {
+            MONTHS.contains(*c)
+        } else {
+            c.is_ascii_uppercase()
+        };
+
+        if !ok {
+            return error();
+        }
+    }
+
+    Ok(())
+}
+
+// Digits that have been replaced because of omocodia are turned back
+// into digits before being interpreted
+fn digits(code: &[char], start: usize, end: usize) -> u32 {
+    code[start..end].iter().fold(0, |value, c| {
+        let digit = c.to_digit(10).or_else(|| {
+            OMOCODIA.find(*c).map(|d| d as u32)
+        }).unwrap_or(0);
+
+        value * 10 + digit
+    })
+}
+
+// Only two digits of the year are available, so it's assumed the
+// person has been born in the last hundred years
+fn check_birth_date(code: &[char]) -> Result<(), String> {
+    let year = digits(code, 6, 8) as i32;
+    let month = MONTHS.find(code[8]).unwrap_or(0) as u32 + 1;
+    let mut day = digits(code, 9, 11);
+
+    if day > 40 {
+        day -= 40;
+    }
+
+    // 2000 is a leap year, same as 1900 would have been if it were
+    // divisible by 400, so any century works for checking the day
+    match chrono::NaiveDate::from_ymd_opt(2000 + year, month, day) {
+        Some(_) => Ok(()),
+        None => Err(String::from("Invalid date of birth in codice fiscale")),
+    }
+}
+
+fn check_character(code: &[char]) -> char {
+    let sum: u32 = code.iter().enumerate().map(|(i, c)| {
+        let value = match c.to_digit(10) {
+            Some(digit) => digit,
+            None => *c as u32 - 'A' as u32,
+        };
+
+        // Positions are counted from one in the specification
+        if i % 2 == 0 {
+            ODD_VALUES[value as usize]
+        } else {
+            value
+        }
+    }).sum();
+
+    (b'A' + (sum % 26) as u8) as char
+}
+
+fn letters(text: &str) -> (Vec<char>, Vec<char>) {
+    let text: Vec<char> = needle::fold(text).to_uppercase().chars().filter(|c| {
+        c.is_ascii_uppercase()
+    }).collect();
+
+    text.iter().partition(|c| !"AEIOU".contains(**c))
+}
+
+fn pad(chars: impl Iterator<Item = char>) -> String {
+    chars.chain("XXX".chars()).take(3).collect()
+}
+
+// Consonants first, then vowels, padded with X
+fn surname_code(surname: &str) -> String {
+    let (consonants, vowels) = letters(surname);
+
+    pad(consonants.into_iter().chain(vowels))
+}
+
+// Same as the surname, except the second consonant is skipped if
+// there are more than three
+fn given_names_code(given_names: &str) -> String {
+    let (consonants, vowels) = letters(given_names);
+
+    if consonants.len() > 3 {
+        return [consonants[0], consonants[2], consonants[3]].iter().collect();
+    }
+
+    pad(consonants.into_iter().chain(vowels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(value: &str, people: &str, cross_check: bool) -> Result<(), String> {
+        let mut record: [String; Column::SIZE] = Default::default();
+        record[usize::from(Column::People)] = String::from(people);
+
+        CodiceFiscale::new(cross_check).validate(value, &record)
+    }
+
+    #[test]
+    fn valid() {
+        assert!(validate("RSSMRA85T10A562S", "", false).is_ok());
+        assert!(validate(" rssmra85t10a562s ", "", false).is_ok());
+    }
+
+    #[test]
+    fn female() {
+        // Born on the 10th, with 40 added to the day
+        assert!(validate("RSSMRA85T50A562W", "", false).is_ok());
+        assert!(validate("BNCNNA90A41F205W", "", false).is_ok());
+    }
+
+    #[test]
+    fn omocodia() {
+        assert!(validate("RSSMRA85T10A56NH", "", false).is_ok());
+        assert!(validate("RSSMRA85T10A5SNT", "", false).is_ok());
+    }
+
+    #[test]
+    fn wrong_check_character() {
+        assert_eq!(
+            validate("RSSMRA85T10A562X", "", false),
+            Err(String::from("Wrong check character in codice fiscale, should be S")),
+        );
+    }
+
+    #[test]
+    fn wrong_format() {
+        assert!(validate("RSSMRA85T10A562", "", false).is_err());
+        assert!(validate("RSSMRA85Z10A562S", "", false).is_err());
+        assert!(validate("RSSMRA8XT10A562S", "", false).is_err());
+        assert!(validate("1SSMRA85T10A562S", "", false).is_err());
+    }
+
+    #[test]
+    fn wrong_birth_date() {
+        // February 30th
+        assert_eq!(
+            validate("RSSMRA85B30A562S", "", false).unwrap_err(),
+            "Invalid date of birth in codice fiscale",
+        );
+        // Day 72, which would be the 32nd for a woman
+        assert_eq!(
+            validate("RSSMRA85T72A562S", "", false).unwrap_err(),
+            "Invalid date of birth in codice fiscale",
+        );
+    }
+
+    #[test]
+    fn cross_check() {
+        assert!(validate("RSSMRA85T10A562S", "Rossi Mario", true).is_ok());
+        assert!(validate("RSSMRA85T10A562S", "Bianchi Anna; Rossi Mario", true).is_ok());
+        assert!(validate("RSSMRA85T10A562S", "Bianchi Anna", true).is_err());
+    }
+
+    #[test]
+    fn codes_from_names() {
+        assert_eq!(surname_code("Rossi"), "RSS");
+        assert_eq!(surname_code("Fo"), "FOX");
+        assert_eq!(given_names_code("Mario"), "MRA");
+        assert_eq!(given_names_code("Gianfranco"), "GFR");
+    }
+}
@@ -2,6 +2,9 @@
 // Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
 // SPDX-License-Identifier: GPL-2.0-or-later
 
+use std::cmp;
+use std::cmp::Ordering;
+
 #[derive(Clone)]
 pub enum Column {
     Date,
@@ -14,6 +17,97 @@ pub enum Column {
 
 impl Column {
     pub const SIZE: usize = 6;
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Date => "date",
+            Column::Number => "number",
+            Column::People => "people",
+            Column::Signature => "signature",
+            Column::Flags => "flags",
+            Column::ID => "id",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Column::Date => "Date",
+            Column::Number => "Number",
+            Column::People => "People",
+            Column::Signature => "Signature",
+            Column::Flags => "Flags",
+            Column::ID => "ID",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Column> {
+        (0..Column::SIZE).map(Column::from).find(|column| {
+            column.name() == name
+        })
+    }
+
+    // Compares two values taking the type of the column into account,
+    // so that numbers and dates are sorted naturally. Values that
+    // can't be parsed are sorted after all valid ones
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            Column::Number => {
+                compare_parsed(a.parse::<i32>().ok(), b.parse::<i32>().ok())
+                .then_with(|| collate(a, b))
+            },
+            Column::Date => {
+                compare_parsed(parse_date(a), parse_date(b))
+                .then_with(|| collate(a, b))
+            },
+            _ => collate(a, b),
+        }
+    }
+
+    // Sorts records by the value they have in this column, keeping
+    // the original order for records that compare as equal
+    pub fn sort(&self, records: &mut [[String; Column::SIZE]], descending: bool) {
+        let index = usize::from(self.clone());
+
+        records.sort_by(|a, b| {
+            let ret = self.compare(&a[index], &b[index]);
+            if descending { ret.reverse() } else { ret }
+        });
+    }
+}
+
+// Dates are usually entered in the short format used by the
+// insert action, but the long one is accepted too. The order
+// matters, because %Y would happily parse "21" as year 21 AD
+pub fn parse_date(text: &str) -> Option<chrono::NaiveDate> {
+    let text = text.trim();
+
+    chrono::NaiveDate::parse_from_str(text, "%d/%m/%y")
+    .or_else(|_| chrono::NaiveDate::parse_from_str(text, "%d/%m/%Y"))
+    .ok()
+}
+
+// The number following the highest one in use, which is what new
+// records get by default. Values that are not numbers are ignored
+pub fn next_number<S: AsRef<str>>(numbers: impl IntoIterator<Item = S>) -> i32 {
+    numbers.into_iter().fold(1, |next, value| {
+        match value.as_ref().parse::<i32>() {
+            Ok(value) => cmp::max(next, value + 1),
+            Err(_) => next,
+        }
+    })
+}
+
+fn compare_parsed<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn collate(a: &str, b: &str) -> Ordering {
+    glib::CollationKey::from(a).cmp(&glib::CollationKey::from(b))
 }
 
 impl From<Column> for u8 {
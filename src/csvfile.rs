@@ -0,0 +1,52 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::path::Path;
+
+use crate::column::Column;
+use crate::liststore::ListStore;
+
+pub fn read(path: &Path) -> csv::Result<Vec<[String; Column::SIZE]>> {
+    let mut reader = csv::ReaderBuilder::new()
+                     .has_headers(false)
+                     .from_path(path)?;
+
+    // Grab the good records only
+    let records = reader.records().filter(|r| r.is_ok()).flatten();
+
+    let mut ret = Vec::new();
+
+    for record in records {
+        ret.push(record_from_fields(record.iter()));
+    }
+
+    Ok(ret)
+}
+
+// Fields are expected in the same order as columns. Missing fields
+// default to the empty string, so that it's possible to load files
+// created using older versions of the application
+pub fn record_from_fields<'a>(fields: impl IntoIterator<Item = &'a str>) -> [String; Column::SIZE] {
+    let mut values = ListStore::new_row();
+
+    for (value, field) in values.iter_mut().zip(fields) {
+        *value = String::from(field);
+    }
+
+    values
+}
+
+pub fn write(path: &Path, records: &[[String; Column::SIZE]]) -> csv::Result<()> {
+    let mut writer = csv::WriterBuilder::new()
+                     .has_headers(false)
+                     .from_path(path)?;
+
+    for record in records {
+        writer.write_record(record)?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
@@ -0,0 +1,275 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::column::Column;
+use crate::csvfile;
+use crate::history::Entry;
+use crate::ledger;
+use crate::ledger::Payment;
+use crate::signature::Signature;
+use crate::storage;
+use crate::storage::Storage;
+
+// Records are stored one per line, with fields in the same order as
+// columns. The whole file is rewritten on every save.
+//
+// Everything else is stored next to the document, in files and
+// directories with the same name and a different extension
+pub struct CsvStorage {
+    path: PathBuf,
+}
+
+impl CsvStorage {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    fn payments_path(&self) -> PathBuf {
+        self.path.with_extension("payments.csv")
+    }
+
+    // Card numbers and event names are chosen by the user, so make
+    // sure they can't point outside of the directory
+    fn file_name(text: &str, extension: &str) -> String {
+        let name: String = text.chars().map(|c| {
+            if c == '/' || c == '\\' { '-' } else { c }
+        }).collect();
+
+        format!("{}.{}", name.trim_start_matches('.'), extension)
+    }
+
+    fn signature_path(&self, number: &str) -> PathBuf {
+        let mut ret = self.path.with_extension("signatures");
+        ret.push(CsvStorage::file_name(number, "sig"));
+        ret
+    }
+
+    // One file per event
+    fn attendance_dir(&self) -> PathBuf {
+        self.path.with_extension("attendance")
+    }
+
+    fn attendance_path(&self, event: &str) -> PathBuf {
+        let mut ret = self.attendance_dir();
+        ret.push(CsvStorage::file_name(event, "csv"));
+        ret
+    }
+
+    fn history_path(&self) -> PathBuf {
+        self.path.with_extension("history")
+    }
+
+    // A missing file simply means nothing has been stored yet
+    fn reader(path: &Path) -> Result<Option<csv::Reader<fs::File>>, storage::Error> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let reader = csv::ReaderBuilder::new()
+                     .has_headers(false)
+                     .flexible(true)
+                     .from_path(path)?;
+
+        Ok(Some(reader))
+    }
+
+    fn appender(path: &Path) -> io::Result<fs::File> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+    }
+}
+
+impl Storage for CsvStorage {
+    fn load(&self) -> Result<Vec<[String; Column::SIZE]>, storage::Error> {
+        Ok(csvfile::read(&self.path)?)
+    }
+
+    fn save(&self, records: &[[String; Column::SIZE]]) -> Result<(), storage::Error> {
+        Ok(csvfile::write(&self.path, records)?)
+    }
+
+    fn load_payments(&self) -> Result<Vec<Payment>, storage::Error> {
+        let mut ret = Vec::new();
+
+        let mut reader = match CsvStorage::reader(&self.payments_path())? {
+            Some(reader) => reader,
+            None => return Ok(ret),
+        };
+
+        // Skipping anything that can't be read would mean losing it
+        // the next time payments are saved
+        for record in reader.records() {
+            let record = record?;
+            let field = |i| String::from(record.get(i).unwrap_or_default());
+
+            let amount = match ledger::parse_amount(&field(2)) {
+                Some(amount) => amount,
+                None => {
+                    let line = record.position().map_or(0, |position| position.line());
+                    return Err(storage::Error::Invalid(format!(
+                        "Invalid amount \"{}\" on line {} of {}",
+                        field(2),
+                        line,
+                        self.payments_path().display(),
+                    )));
+                },
+            };
+
+            ret.push(Payment {
+                number: field(0),
+                date: field(1),
+                amount,
+                method: field(3),
+                receipt: field(4),
+                operator: field(5),
+                id: field(6),
+            });
+        }
+
+        Ok(ret)
+    }
+
+    fn save_payments(&self, payments: &[Payment]) -> Result<(), storage::Error> {
+        let mut writer = csv::WriterBuilder::new()
+                         .has_headers(false)
+                         .from_path(self.payments_path())?;
+
+        for payment in payments {
+            writer.write_record(&[
+                &payment.number,
+                &payment.date,
+                &ledger::format_amount(payment.amount),
+                &payment.method,
+                &payment.receipt,
+                &payment.operator,
+                &payment.id,
+            ])?;
+        }
+
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    fn load_signature(&self, number: &str) -> Result<Option<Signature>, storage::Error> {
+        match fs::read_to_string(self.signature_path(number)) {
+            Ok(text) => Ok(Signature::parse(&text)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save_signature(&self, number: &str, signature: &Signature) -> Result<(), storage::Error> {
+        let path = self.signature_path(number);
+
+        if signature.is_empty() {
+            return match fs::remove_file(&path) {
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+                other => Ok(other?),
+            };
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        Ok(fs::write(&path, signature.to_text())?)
+    }
+
+    fn events(&self) -> Result<Vec<String>, storage::Error> {
+        let mut ret = Vec::new();
+
+        let entries = match fs::read_dir(self.attendance_dir()) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(ret),
+            Err(e) => return Err(e.into()),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+
+            if path.extension().and_then(|e| e.to_str()) == Some("csv") {
+                if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                    ret.push(String::from(stem));
+                }
+            }
+        }
+
+        ret.sort();
+
+        Ok(ret)
+    }
+
+    fn load_attendance(&self, event: &str) -> Result<Vec<(String, String)>, storage::Error> {
+        let mut ret = Vec::new();
+
+        let mut reader = match CsvStorage::reader(&self.attendance_path(event))? {
+            Some(reader) => reader,
+            None => return Ok(ret),
+        };
+
+        for record in reader.records().flatten() {
+            let number = record.get(0).unwrap_or_default();
+            let time = record.get(1).unwrap_or_default();
+
+            if !number.is_empty() {
+                ret.push((String::from(number), String::from(time)));
+            }
+        }
+
+        Ok(ret)
+    }
+
+    fn add_attendance(&self, event: &str, number: &str, time: &str) -> Result<(), storage::Error> {
+        let file = CsvStorage::appender(&self.attendance_path(event))?;
+
+        let mut writer = csv::WriterBuilder::new()
+                         .has_headers(false)
+                         .from_writer(file);
+
+        writer.write_record(&[number, time])?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    // Entries are stored one per line, with the time and the message
+    // separated by a tab
+    fn load_history(&self) -> Result<Vec<Entry>, storage::Error> {
+        let text = match fs::read_to_string(self.history_path()) {
+            Ok(text) => text,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(text.lines().filter_map(|line| {
+            let (time, message) = line.split_at(line.find('\t')?);
+
+            Some(Entry {
+                time: String::from(time),
+                message: String::from(&message[1..]),
+            })
+        }).collect())
+    }
+
+    fn append_history(&self, entry: &Entry) -> Result<(), storage::Error> {
+        let mut file = CsvStorage::appender(&self.history_path())?;
+
+        Ok(writeln!(file, "{}\t{}", entry.time, entry.message)?)
+    }
+}
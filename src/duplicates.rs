@@ -0,0 +1,288 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use chrono::Datelike;
+
+use crate::column;
+use crate::column::Column;
+use crate::needle;
+
+// How similar two names have to be, as the share of the words in
+// either of them that they have in common, for the records to be
+// considered possible duplicates
+const SIMILARITY: f64 = 0.66;
+
+// Records that might refer to the same member, by their position in
+// the registry
+pub struct Group {
+    pub records: Vec<usize>,
+    // Whether at least two of the records have the same ID, which
+    // makes it much more likely that they're actual duplicates
+    pub same_id: bool,
+}
+
+impl Group {
+    // Identifies the group across sessions, so that it's possible to
+    // remember it has been dismissed. Should more records join the
+    // group later, it will be suggested again
+    pub fn key(&self, records: &[[String; Column::SIZE]]) -> String {
+        let mut numbers: Vec<&str> = self.records.iter().map(|&i| {
+            records[i][usize::from(Column::Number)].as_str()
+        }).collect();
+        numbers.sort_unstable();
+
+        numbers.join(",")
+    }
+}
+
+// Words in the name, ignoring case, accents, punctuation and order,
+// so that "Rossi Mario" and "Mario Rossì" are the same
+fn words(people: &str) -> BTreeSet<String> {
+    needle::fold(people).split(|c: char| !c.is_alphanumeric()).filter(|word| {
+        !word.is_empty()
+    }).map(String::from).collect()
+}
+
+fn similarity(a: &BTreeSet<String>, b: &BTreeSet<String>) -> f64 {
+    let union = a.union(b).count();
+
+    if union == 0 {
+        return 0.0;
+    }
+
+    a.intersection(b).count() as f64 / union as f64
+}
+
+fn normalize_id(id: &str) -> String {
+    id.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_uppercase).collect()
+}
+
+fn find_root(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+
+    // Shorten the path for next time
+    let mut i = i;
+    while parents[i] != root {
+        let next = parents[i];
+        parents[i] = root;
+        i = next;
+    }
+
+    root
+}
+
+// Joins the groups the two records belong to, unless they contain
+// records with different IDs: since names only need to be similar,
+// a record without ID could otherwise bring together two different
+// people
+fn union(parents: &mut [usize], group_ids: &mut [&str], i: usize, j: usize) {
+    let a = find_root(parents, i);
+    let b = find_root(parents, j);
+
+    if a == b {
+        return;
+    }
+    if !group_ids[a].is_empty() && !group_ids[b].is_empty() && group_ids[a] != group_ids[b] {
+        return;
+    }
+
+    parents[b] = a;
+    if group_ids[a].is_empty() {
+        group_ids[a] = group_ids[b];
+    }
+}
+
+// A new card is issued to every member each year, so the same name
+// showing up in different years is expected: only records issued in
+// the same year are compared. Records are possible duplicates if they
+// have the same ID, or similar names and no conflicting IDs
+pub fn find(records: &[[String; Column::SIZE]]) -> Vec<Group> {
+    let years: Vec<Option<i32>> = records.iter().map(|record| {
+        column::parse_date(&record[usize::from(Column::Date)]).map(|date| date.year())
+    }).collect();
+    let names: Vec<BTreeSet<String>> = records.iter().map(|record| {
+        words(&record[usize::from(Column::People)])
+    }).collect();
+    let ids: Vec<String> = records.iter().map(|record| {
+        normalize_id(&record[usize::from(Column::ID)])
+    }).collect();
+
+    // Only records sharing at least a word, or the ID, need to be
+    // compared with each other
+    let mut by_word: HashMap<(Option<i32>, &str), Vec<usize>> = HashMap::new();
+    let mut by_id: HashMap<(Option<i32>, &str), Vec<usize>> = HashMap::new();
+
+    for i in 0..records.len() {
+        for word in &names[i] {
+            by_word.entry((years[i], word)).or_default().push(i);
+        }
+        if !ids[i].is_empty() {
+            by_id.entry((years[i], &ids[i])).or_default().push(i);
+        }
+    }
+
+    let mut parents: Vec<usize> = (0..records.len()).collect();
+    let mut group_ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+    let mut compared = HashSet::new();
+
+    for candidates in by_id.values() {
+        for &i in &candidates[1..] {
+            union(&mut parents, &mut group_ids, candidates[0], i);
+        }
+    }
+
+    for candidates in by_word.values() {
+        for (n, &i) in candidates.iter().enumerate() {
+            for &j in &candidates[n + 1..] {
+                if !compared.insert((i, j)) {
+                    continue;
+                }
+
+                // Different IDs mean different people, even if they
+                // happen to have the same name
+                if similarity(&names[i], &names[j]) >= SIMILARITY {
+                    union(&mut parents, &mut group_ids, i, j);
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..records.len() {
+        let root = find_root(&mut parents, i);
+        groups.entry(root).or_default().push(i);
+    }
+
+    let mut ret: Vec<Group> = groups.into_iter().filter(|(_, members)| {
+        members.len() > 1
+    }).map(|(_, members)| {
+        let mut seen = HashSet::new();
+        let same_id = members.iter().any(|&i| !ids[i].is_empty() && !seen.insert(&ids[i]));
+
+        Group {
+            records: members,
+            same_id,
+        }
+    }).collect();
+
+    ret.sort_by_key(|group| group.records[0]);
+    ret
+}
+
+// The first record is kept, with any empty field filled in using the
+// values found in the other ones
+pub fn merge(records: &[&[String; Column::SIZE]]) -> [String; Column::SIZE] {
+    let mut ret = records[0].clone();
+
+    for record in &records[1..] {
+        for (value, other) in ret.iter_mut().zip(record.iter()) {
+            if value.trim().is_empty() {
+                *value = other.clone();
+            }
+        }
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(date: &str, number: &str, people: &str, id: &str) -> [String; Column::SIZE] {
+        let mut ret: [String; Column::SIZE] = Default::default();
+        ret[usize::from(Column::Date)] = String::from(date);
+        ret[usize::from(Column::Number)] = String::from(number);
+        ret[usize::from(Column::People)] = String::from(people);
+        ret[usize::from(Column::ID)] = String::from(id);
+        ret
+    }
+
+    fn groups(records: &[[String; Column::SIZE]]) -> Vec<Vec<usize>> {
+        find(records).into_iter().map(|group| group.records).collect()
+    }
+
+    #[test]
+    fn similar_names() {
+        let records = [
+            record("10/01/21", "1", "Rossi Mario", ""),
+            record("12/03/21", "2", "Bianchi Anna", ""),
+            record("15/05/21", "3", "Mario Rossì", ""),
+        ];
+
+        let found = find(&records);
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].records, vec![0, 2]);
+        assert!(!found[0].same_id);
+    }
+
+    #[test]
+    fn same_id() {
+        let records = [
+            record("10/01/21", "1", "Rossi Mario", "RSSMRA85T10A562S"),
+            record("15/05/21", "2", "Rossi M.", "rssmra85t10a562s"),
+        ];
+
+        let found = find(&records);
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].records, vec![0, 1]);
+        assert!(found[0].same_id);
+    }
+
+    #[test]
+    fn different_ids() {
+        let records = [
+            record("10/01/21", "1", "Rossi Mario", "RSSMRA85T10A562S"),
+            record("15/05/21", "2", "Rossi Mario", "RSSMRA90A01F205X"),
+        ];
+
+        assert!(groups(&records).is_empty());
+    }
+
+    #[test]
+    fn different_ids_through_missing_id() {
+        let records = [
+            record("10/01/21", "1", "Rossi Mario", "RSSMRA85T10A562S"),
+            record("12/03/21", "2", "Mario Rossi", ""),
+            record("15/05/21", "3", "Rossi Mario", "RSSMRA90A01F205X"),
+        ];
+
+        let found = groups(&records);
+
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].len(), 2);
+        assert!(found[0].contains(&1));
+    }
+
+    #[test]
+    fn different_years() {
+        let records = [
+            record("10/01/20", "1", "Rossi Mario", "RSSMRA85T10A562S"),
+            record("15/01/21", "1", "Rossi Mario", "RSSMRA85T10A562S"),
+        ];
+
+        assert!(groups(&records).is_empty());
+    }
+
+    #[test]
+    fn merge_fills_empty_values() {
+        let first = record("10/01/21", "1", "Rossi Mario", "");
+        let second = record("15/05/21", "3", "Mario Rossì", "RSSMRA85T10A562S");
+
+        let merged = merge(&[&first, &second]);
+
+        assert_eq!(merged[usize::from(Column::Number)], "1");
+        assert_eq!(merged[usize::from(Column::People)], "Rossi Mario");
+        assert_eq!(merged[usize::from(Column::ID)], "RSSMRA85T10A562S");
+    }
+}
@@ -0,0 +1,145 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use ::gtk::prelude::*;
+
+use crate::column::Column;
+use crate::duplicates::Group;
+
+pub enum Decision {
+    // Leave the records alone for now, and suggest them again next
+    // time
+    Undecided,
+    // Merge all records into the first one
+    Merge,
+    // The records refer to different people, so they should not be
+    // suggested again
+    Dismiss,
+}
+
+#[derive(Clone)]
+struct Row {
+    undecided: gtk::RadioButton,
+    merge: gtk::RadioButton,
+    dismiss: gtk::RadioButton,
+}
+
+#[derive(Clone)]
+pub struct DuplicatesDialog {
+    parent: gtk::Dialog,
+    rows: Vec<Row>,
+}
+
+impl DuplicatesDialog {
+    pub fn new<W: IsA<gtk::Window>>(window: &W, records: &[[String; Column::SIZE]], groups: &[Group]) -> Self {
+        let ret = Self {
+            parent: gtk::Dialog::with_buttons(
+                Some("Possible duplicates"),
+                Some(window),
+                gtk::DialogFlags::MODAL,
+                &[("Cancel", gtk::ResponseType::Cancel), ("Apply", gtk::ResponseType::Ok)],
+            ),
+            rows: groups.iter().map(|_| {
+                let undecided = gtk::RadioButton::with_label("Decide later");
+                Row {
+                    merge: gtk::RadioButton::with_label_from_widget(&undecided, "Merge"),
+                    dismiss: gtk::RadioButton::with_label_from_widget(&undecided, "Not duplicates"),
+                    undecided,
+                }
+            }).collect(),
+        };
+        ret.setup(records, groups);
+        ret
+    }
+
+    fn setup(&self, records: &[[String; Column::SIZE]], groups: &[Group]) {
+        self.parent.set_default_response(gtk::ResponseType::Ok);
+        self.parent.set_default_size(600, 500);
+
+        let list = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        list.set_border_width(12);
+
+        let summary = gtk::Label::new(Some(&format!(
+            "{} groups of records might refer to the same member. Merged records are combined into the first one of their group.",
+            groups.len(),
+        )));
+        summary.set_xalign(0.0);
+        summary.set_line_wrap(true);
+        list.pack_start(&summary, false, false, 0);
+
+        for (group, row) in groups.iter().zip(self.rows.iter()) {
+            let reason = if group.same_id { "Same ID" } else { "Similar names" };
+
+            let mut text = format!("<b>{}</b>\n", reason);
+            for &i in &group.records {
+                let record = &records[i];
+                text.push_str(&glib::markup_escape_text(&format!(
+                    "N. {}  {}  {}  {}\n",
+                    record[usize::from(Column::Number)],
+                    record[usize::from(Column::People)],
+                    record[usize::from(Column::ID)],
+                    record[usize::from(Column::Date)],
+                )));
+            }
+
+            let label = gtk::Label::new(None);
+            label.set_markup(text.trim_end());
+            label.set_xalign(0.0);
+            label.set_selectable(true);
+            label.set_ellipsize(pango::EllipsizeMode::End);
+
+            // Leaving the records alone is the default
+            let buttons = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+            buttons.pack_start(&row.undecided, false, false, 0);
+            buttons.pack_start(&row.merge, false, false, 0);
+            buttons.pack_start(&row.dismiss, false, false, 0);
+
+            let contents = gtk::Box::new(gtk::Orientation::Vertical, 6);
+            contents.set_border_width(6);
+            contents.pack_start(&label, false, false, 0);
+            contents.pack_start(&buttons, false, false, 0);
+
+            let frame = gtk::Frame::new(None);
+            frame.add(&contents);
+
+            list.pack_start(&frame, false, false, 0);
+        }
+
+        let auto_adj: Option<&gtk::Adjustment> = None;
+        let scrolled = gtk::ScrolledWindow::new(auto_adj, auto_adj);
+        scrolled.set_vexpand(true);
+        scrolled.add(&list);
+
+        self.parent.content_area().add(&scrolled);
+    }
+
+    fn decisions(&self) -> Vec<Decision> {
+        self.rows.iter().map(|row| {
+            if row.merge.is_active() {
+                Decision::Merge
+            } else if row.dismiss.is_active() {
+                Decision::Dismiss
+            } else {
+                Decision::Undecided
+            }
+        }).collect()
+    }
+
+    // Returns a decision for each group, in the same order, or None
+    // if the user has cancelled the operation
+    pub fn run(&self) -> Option<Vec<Decision>> {
+        self.parent.show_all();
+
+        let ret = match self.parent.run() {
+            gtk::ResponseType::Ok => Some(self.decisions()),
+            _ => None,
+        };
+
+        unsafe {
+            self.parent.destroy();
+        }
+
+        ret
+    }
+}
@@ -0,0 +1,328 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ::gtk::prelude::*;
+
+use crate::column::Column;
+use crate::pattern::Pattern;
+
+// Columns that are searched, in the same order they're found in the
+// treeview
+const COLUMNS: [Column; 5] = [
+    Column::Number,
+    Column::People,
+    Column::Signature,
+    Column::ID,
+    Column::Date,
+];
+
+// Columns in the preview model
+const PREVIEW_RECORD: u32 = 0;
+const PREVIEW_COLUMN: u32 = 1;
+const PREVIEW_NUMBER: u32 = 2;
+const PREVIEW_FIELD: u32 = 3;
+const PREVIEW_BEFORE: u32 = 4;
+const PREVIEW_AFTER: u32 = 5;
+
+const RESPONSE_REPLACE: u16 = 1;
+const RESPONSE_REPLACE_ALL: u16 = 2;
+
+// Changes are described by the position of the record in the
+// registry, the column and the new value
+pub type Change = (usize, Column, String);
+
+#[derive(Clone)]
+pub struct FindReplaceDialog {
+    parent: gtk::Dialog,
+    find: gtk::Entry,
+    replace: gtk::Entry,
+    scope: gtk::ComboBoxText,
+    casebutton: gtk::CheckButton,
+    wordbutton: gtk::CheckButton,
+    regexbutton: gtk::CheckButton,
+    status: gtk::Label,
+    preview: gtk::ListStore,
+    previewview: gtk::TreeView,
+    records: Rc<RefCell<Vec<[String; Column::SIZE]>>>,
+}
+
+impl FindReplaceDialog {
+    // All records are searched, regardless of those currently
+    // displayed in the window
+    pub fn new<W: IsA<gtk::Window>>(window: &W, records: Vec<[String; Column::SIZE]>) -> Self {
+        let ret = Self {
+            parent: gtk::Dialog::with_buttons(
+                Some("Find and replace"),
+                Some(window),
+                gtk::DialogFlags::MODAL,
+                &[
+                    ("Close", gtk::ResponseType::Close),
+                    ("Replace", gtk::ResponseType::Other(RESPONSE_REPLACE)),
+                    ("Replace all", gtk::ResponseType::Other(RESPONSE_REPLACE_ALL)),
+                ],
+            ),
+            find: gtk::Entry::new(),
+            replace: gtk::Entry::new(),
+            scope: gtk::ComboBoxText::new(),
+            casebutton: gtk::CheckButton::with_label("Match case"),
+            wordbutton: gtk::CheckButton::with_label("Whole words only"),
+            regexbutton: gtk::CheckButton::with_label("Regular expression"),
+            status: gtk::Label::new(None),
+            preview: gtk::ListStore::new(&[
+                glib::Type::U32,
+                glib::Type::U32,
+                glib::Type::STRING,
+                glib::Type::STRING,
+                glib::Type::STRING,
+                glib::Type::STRING,
+            ]),
+            previewview: gtk::TreeView::new(),
+            records: Rc::new(RefCell::new(records)),
+        };
+        ret.setup();
+        ret
+    }
+
+    fn setup(&self) {
+        self.parent.set_default_size(650, 450);
+
+        self.scope.append(Some("all"), "All fields");
+        for column in &COLUMNS {
+            self.scope.append(Some(column.name()), column.title());
+        }
+        self.scope.set_active(Some(0));
+
+        let _self = self.clone();
+        self.scope.connect_changed(move |_| {
+            _self.options_changed();
+        });
+
+        for entry in &[&self.find, &self.replace] {
+            entry.set_hexpand(true);
+
+            let _self = self.clone();
+            entry.connect_changed(move |_| {
+                _self.options_changed();
+            });
+        }
+
+        for button in &[&self.casebutton, &self.wordbutton, &self.regexbutton] {
+            let _self = self.clone();
+            button.connect_toggled(move |_| {
+                _self.options_changed();
+            });
+        }
+
+        let label = |text: &str| {
+            let label = gtk::Label::new(Some(text));
+            label.set_xalign(1.0);
+            label.style_context().add_class("dim-label");
+            label
+        };
+
+        let options = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        options.pack_start(&self.casebutton, false, false, 0);
+        options.pack_start(&self.wordbutton, false, false, 0);
+        options.pack_start(&self.regexbutton, false, false, 0);
+
+        let grid = gtk::Grid::new();
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(12);
+        grid.attach(&label("Find"), 0, 0, 1, 1);
+        grid.attach(&self.find, 1, 0, 1, 1);
+        grid.attach(&label("Replace with"), 0, 1, 1, 1);
+        grid.attach(&self.replace, 1, 1, 1, 1);
+        grid.attach(&label("In"), 0, 2, 1, 1);
+        grid.attach(&self.scope, 1, 2, 1, 1);
+        grid.attach(&options, 1, 3, 1, 1);
+
+        let columns = [
+            ("Number", PREVIEW_NUMBER),
+            ("Field", PREVIEW_FIELD),
+            ("Before", PREVIEW_BEFORE),
+            ("After", PREVIEW_AFTER),
+        ];
+        for (title, index) in &columns {
+            let renderer = gtk::CellRendererText::new();
+            renderer.set_ellipsize(pango::EllipsizeMode::End);
+            let column = gtk::TreeViewColumn::new();
+            column.set_title(title);
+            column.set_resizable(true);
+            column.set_expand(*index == PREVIEW_BEFORE || *index == PREVIEW_AFTER);
+            column.pack_start(&renderer, true);
+            column.add_attribute(&renderer, "text", *index as i32);
+            self.previewview.append_column(&column);
+        }
+        self.previewview.set_model(Some(&self.preview));
+        self.previewview.selection().set_mode(gtk::SelectionMode::Multiple);
+
+        let auto_adj: Option<&gtk::Adjustment> = None;
+        let scrolled = gtk::ScrolledWindow::new(auto_adj, auto_adj);
+        scrolled.set_shadow_type(gtk::ShadowType::In);
+        scrolled.add(&self.previewview);
+
+        self.status.set_xalign(0.0);
+        self.status.set_line_wrap(true);
+
+        let contents = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        contents.set_border_width(12);
+        contents.pack_start(&grid, false, false, 0);
+        contents.pack_start(&self.status, false, false, 0);
+        contents.pack_start(&scrolled, true, true, 0);
+        self.parent.content_area().add(&contents);
+
+        self.update_preview();
+    }
+
+    fn columns(&self) -> Vec<Column> {
+        match self.scope.active_id().and_then(|id| Column::from_name(&id)) {
+            Some(column) => vec![column],
+            None => COLUMNS.to_vec(),
+        }
+    }
+
+    // Returns None if there's nothing to look for yet, or an error
+    // if the regular expression is not valid
+    fn pattern(&self) -> Option<Result<Pattern, regex::Error>> {
+        let find = self.find.text();
+
+        if find.is_empty() {
+            return None;
+        }
+
+        Some(Pattern::new(
+            &find,
+            &self.replace.text(),
+            self.casebutton.is_active(),
+            self.wordbutton.is_active(),
+            self.regexbutton.is_active(),
+        ))
+    }
+
+    // Lists every value that would be changed, along with what it
+    // would be changed to
+    fn update_preview(&self) {
+        self.preview.clear();
+
+        let pattern = match self.pattern() {
+            Some(Ok(pattern)) => pattern,
+            Some(Err(e)) => {
+                self.status.set_text(&format!("Invalid regular expression: {}", e));
+                self.set_can_replace(false);
+                return;
+            },
+            None => {
+                self.status.set_text("");
+                self.set_can_replace(false);
+                return;
+            },
+        };
+
+        let columns = self.columns();
+        let records = self.records.borrow();
+        let mut count = 0;
+
+        for (i, record) in records.iter().enumerate() {
+            for column in &columns {
+                let before = &record[usize::from(column.clone())];
+
+                if !pattern.is_match(before) {
+                    continue;
+                }
+
+                let after = pattern.replace(before);
+                let row: [(u32, &dyn glib::ToValue); 6] = [
+                    (PREVIEW_RECORD, &(i as u32)),
+                    (PREVIEW_COLUMN, &u32::from(column.clone())),
+                    (PREVIEW_NUMBER, &record[usize::from(Column::Number)]),
+                    (PREVIEW_FIELD, &column.title()),
+                    (PREVIEW_BEFORE, before),
+                    (PREVIEW_AFTER, &after),
+                ];
+                self.preview.insert_with_values(None, &row);
+
+                count += 1;
+            }
+        }
+
+        self.status.set_text(&match count {
+            0 => String::from("No matches"),
+            1 => String::from("1 match"),
+            n => format!("{} matches", n),
+        });
+        self.set_can_replace(count > 0);
+    }
+
+    fn set_can_replace(&self, sensitive: bool) {
+        self.parent.set_response_sensitive(gtk::ResponseType::Other(RESPONSE_REPLACE), sensitive);
+        self.parent.set_response_sensitive(gtk::ResponseType::Other(RESPONSE_REPLACE_ALL), sensitive);
+    }
+
+    fn change(&self, iter: &gtk::TreeIter) -> Option<Change> {
+        let model = &self.preview;
+        let record: u32 = model.value(iter, PREVIEW_RECORD as i32).get().ok()?;
+        let column: u32 = model.value(iter, PREVIEW_COLUMN as i32).get().ok()?;
+        let after: String = model.value(iter, PREVIEW_AFTER as i32).get().ok()?;
+
+        Some((record as usize, Column::from(column), after))
+    }
+
+    // Replace works on the matches selected in the preview or, if
+    // none is selected, on the first one
+    fn selected_changes(&self) -> Vec<Change> {
+        let (paths, _) = self.previewview.selection().selected_rows();
+
+        let mut iters: Vec<gtk::TreeIter> = paths.iter().filter_map(|path| self.preview.iter(path)).collect();
+        if iters.is_empty() {
+            iters.extend(self.preview.iter_first());
+        }
+
+        iters.iter().filter_map(|iter| self.change(iter)).collect()
+    }
+
+    fn all_changes(&self) -> Vec<Change> {
+        let mut ret = Vec::new();
+
+        if let Some(iter) = self.preview.iter_first() {
+            loop {
+                ret.extend(self.change(&iter));
+
+                if !self.preview.iter_next(&iter) { break; }
+            }
+        }
+
+        ret
+    }
+
+    // The dialog stays open until the user closes it: every time
+    // some values are replaced, the changes are passed to apply(),
+    // which returns the updated records
+    pub fn run<F: Fn(Vec<Change>) -> Vec<[String; Column::SIZE]>>(&self, apply: F) {
+        self.parent.show_all();
+
+        loop {
+            let changes = match self.parent.run() {
+                gtk::ResponseType::Other(RESPONSE_REPLACE) => self.selected_changes(),
+                gtk::ResponseType::Other(RESPONSE_REPLACE_ALL) => self.all_changes(),
+                _ => break,
+            };
+
+            *self.records.borrow_mut() = apply(changes);
+            self.update_preview();
+        }
+
+        unsafe {
+            self.parent.destroy();
+        }
+    }
+
+    // Signal handlers
+
+    fn options_changed(&self) {
+        self.update_preview();
+    }
+}
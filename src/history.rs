@@ -0,0 +1,21 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+// Events that can't be told from the registry itself, such as members
+// moving to a different one, are logged along with the time they've
+// happened at
+#[derive(Clone)]
+pub struct Entry {
+    pub time: String,
+    pub message: String,
+}
+
+impl Entry {
+    pub fn new(message: &str) -> Self {
+        Self {
+            time: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            message: String::from(message),
+        }
+    }
+}
@@ -0,0 +1,260 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use chrono::Datelike;
+
+use crate::column;
+use crate::receipt;
+
+// Payment methods offered when recording a payment. Other values
+// found in the ledger are preserved as they are
+pub const METHODS: [&str; 4] = ["Cash", "Bank transfer", "Card", "Other"];
+
+// Amounts are stored in cents, to avoid rounding errors
+pub fn parse_amount(text: &str) -> Option<i64> {
+    let text = text.trim().replace(',', ".");
+    let (units, cents) = match text.find('.') {
+        Some(index) => (&text[..index], &text[index + 1..]),
+        None => (text.as_str(), ""),
+    };
+
+    if units.is_empty() || cents.len() > 2
+       || !units.chars().all(|c| c.is_ascii_digit())
+       || !cents.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let units = units.parse::<i64>().ok()?;
+    let cents = format!("{:0<2}", cents).parse::<i64>().ok()?;
+
+    Some(units * 100 + cents)
+}
+
+pub fn format_amount(amount: i64) -> String {
+    let sign = if amount < 0 { "-" } else { "" };
+
+    format!("{}{}.{:02}", sign, amount.abs() / 100, amount.abs() % 100)
+}
+
+#[derive(Clone)]
+pub struct Payment {
+    // Card number and ID of the member who has paid
+    pub number: String,
+    pub id: String,
+    // Same format as the Date column
+    pub date: String,
+    pub amount: i64,
+    pub method: String,
+    pub receipt: String,
+    // Initials of whoever has collected the payment, same as the
+    // Signature column
+    pub operator: String,
+}
+
+impl Payment {
+    // Card numbers can change, and are not guaranteed to be unique,
+    // so the ID is used to find the member whenever both the payment
+    // and the record have one
+    pub fn belongs_to(&self, number: &str, id: &str) -> bool {
+        if !self.id.trim().is_empty() && !id.trim().is_empty() {
+            self.id.trim().eq_ignore_ascii_case(id.trim())
+        } else {
+            self.number == number
+        }
+    }
+}
+
+// Payments are kept separately from the registry, which has a fixed
+// set of columns, and linked to members through their ID or, for
+// members who don't have one, their card number
+pub struct Ledger {
+    payments: Vec<Payment>,
+}
+
+impl Ledger {
+    pub fn new(payments: Vec<Payment>) -> Self {
+        Self {
+            payments,
+        }
+    }
+
+    pub fn payments_for(&self, number: &str, id: &str) -> Vec<Payment> {
+        self.payments.iter().filter(|p| p.belongs_to(number, id)).cloned().collect()
+    }
+
+    // Replaces all payments recorded for the member
+    pub fn set_payments_for(&mut self, number: &str, id: &str, payments: Vec<Payment>) {
+        self.payments.retain(|p| !p.belongs_to(number, id));
+        self.payments.extend(payments);
+    }
+
+    // Used when records are merged, so that no payment is lost
+    pub fn renumber(&mut self, from: &str, to: &str) {
+        for payment in self.payments.iter_mut().filter(|p| p.number == from) {
+            payment.number = String::from(to);
+        }
+    }
+
+    // Assigns the next receipt number to the n-th payment made by
+    // the member, unless it already has one, and returns it
+    pub fn issue_receipt(&mut self, number: &str, id: &str, index: usize) -> Option<Payment> {
+        let payment = self.payments.iter().filter(|p| p.belongs_to(number, id)).nth(index)?;
+
+        if payment.receipt.is_empty() {
+            let year = column::parse_date(&payment.date).map_or_else(|| {
+                chrono::Local::today().year()
+            }, |date| date.year());
+            let next = receipt::next_number(&self.payments, year);
+
+            let payment = self.payments.iter_mut().filter(|p| p.belongs_to(number, id)).nth(index)?;
+            payment.receipt = next;
+        }
+
+        self.payments.iter().filter(|p| p.belongs_to(number, id)).nth(index).cloned()
+    }
+
+    // The most recent payment made by the member
+    pub fn last_paid(&self, number: &str, id: &str) -> Option<&Payment> {
+        self.payments.iter().filter(|p| p.belongs_to(number, id)).max_by_key(|p| {
+            column::parse_date(&p.date)
+        })
+    }
+
+    // Cards are valid for the year they've been issued in, and the
+    // fee is due once for that year: the balance is what's been paid
+    // during the year minus the fee, so a negative balance means the
+    // member still owes money
+    pub fn balance(&self, number: &str, id: &str, issued: &str, fee: i64) -> i64 {
+        let year = column::parse_date(issued).map(|date| date.year());
+
+        let paid: i64 = self.payments.iter().filter(|p| {
+            p.belongs_to(number, id) && column::parse_date(&p.date).map(|date| date.year()) == year
+        }).map(|p| p.amount).sum();
+
+        paid - fee
+    }
+
+    pub fn payments(&self) -> &[Payment] {
+        &self.payments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payment(number: &str, id: &str, date: &str, amount: i64) -> Payment {
+        Payment {
+            number: String::from(number),
+            id: String::from(id),
+            date: String::from(date),
+            amount,
+            method: String::from("Cash"),
+            receipt: String::new(),
+            operator: String::new(),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_amount("10"), Some(1000));
+        assert_eq!(parse_amount("10,5"), Some(1050));
+        assert_eq!(parse_amount("10.50"), Some(1050));
+        assert_eq!(parse_amount(" 7.05 "), Some(705));
+        assert_eq!(parse_amount("10."), Some(1000));
+        assert_eq!(parse_amount("0,01"), Some(1));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_eq!(parse_amount(""), None);
+        assert_eq!(parse_amount("10.555"), None);
+        assert_eq!(parse_amount(".5"), None);
+        assert_eq!(parse_amount("-5"), None);
+        assert_eq!(parse_amount("1.000,00"), None);
+        assert_eq!(parse_amount("ten"), None);
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!(format_amount(1050), "10.50");
+        assert_eq!(format_amount(5), "0.05");
+        assert_eq!(format_amount(0), "0.00");
+        assert_eq!(format_amount(-1050), "-10.50");
+        assert_eq!(parse_amount(&format_amount(123456)), Some(123456));
+    }
+
+    #[test]
+    fn linked_by_id() {
+        let p = payment("1", "RSSMRA85T10A562S", "10/01/21", 1000);
+
+        assert!(p.belongs_to("1", "RSSMRA85T10A562S"));
+        // The card number doesn't matter when both have an ID
+        assert!(p.belongs_to("7", " rssmra85t10a562s "));
+        assert!(!p.belongs_to("1", "BNCNNA90A41F205W"));
+    }
+
+    #[test]
+    fn linked_by_number() {
+        let p = payment("1", "", "10/01/21", 1000);
+
+        assert!(p.belongs_to("1", ""));
+        assert!(p.belongs_to("1", "RSSMRA85T10A562S"));
+        assert!(!p.belongs_to("2", ""));
+
+        // A record without ID is matched by number
+        let p = payment("1", "RSSMRA85T10A562S", "10/01/21", 1000);
+        assert!(p.belongs_to("1", " "));
+        assert!(!p.belongs_to("2", ""));
+    }
+
+    #[test]
+    fn balance() {
+        let ledger = Ledger::new(vec![
+            payment("1", "", "10/01/21", 1000),
+            payment("1", "", "15/06/21", 500),
+            payment("1", "", "10/01/20", 2000),
+            payment("2", "", "10/01/21", 3000),
+        ]);
+
+        assert_eq!(ledger.balance("1", "", "05/01/21", 2000), -500);
+        assert_eq!(ledger.balance("1", "", "05/01/20", 2000), 0);
+        assert_eq!(ledger.balance("2", "", "05/01/21", 2000), 1000);
+        assert_eq!(ledger.balance("3", "", "05/01/21", 2000), -2000);
+    }
+
+    #[test]
+    fn payments_for() {
+        let mut ledger = Ledger::new(vec![
+            payment("1", "RSSMRA85T10A562S", "10/01/21", 1000),
+            payment("1", "", "10/01/21", 500),
+            payment("2", "", "10/01/21", 3000),
+        ]);
+
+        // After a renumbering, payments with an ID still follow the
+        // member, while those without one stay with the old number
+        assert_eq!(ledger.payments_for("5", "RSSMRA85T10A562S").len(), 1);
+        assert_eq!(ledger.payments_for("1", "").len(), 2);
+
+        ledger.set_payments_for("1", "", vec![payment("1", "", "11/01/21", 700)]);
+        assert_eq!(ledger.payments().len(), 2);
+        assert_eq!(ledger.last_paid("1", "").map(|p| p.amount), Some(700));
+    }
+
+    #[test]
+    fn receipts() {
+        let mut ledger = Ledger::new(vec![
+            payment("1", "", "10/01/21", 1000),
+            payment("1", "", "15/06/21", 500),
+            payment("2", "", "10/01/20", 3000),
+        ]);
+
+        assert_eq!(ledger.issue_receipt("1", "", 1).map(|p| p.receipt), Some(String::from("1/2021")));
+        assert_eq!(ledger.issue_receipt("1", "", 0).map(|p| p.receipt), Some(String::from("2/2021")));
+        // Issuing it again returns the same number
+        assert_eq!(ledger.issue_receipt("1", "", 1).map(|p| p.receipt), Some(String::from("1/2021")));
+        assert_eq!(ledger.issue_receipt("2", "", 0).map(|p| p.receipt), Some(String::from("1/2020")));
+        assert!(ledger.issue_receipt("2", "", 1).is_none());
+    }
+}
@@ -3,10 +3,44 @@
 // SPDX-License-Identifier: GPL-2.0-or-later
 
 mod column;
+mod needle;
+mod pattern;
+mod person;
+mod barcode;
+mod signature;
+mod attendance;
+mod ledger;
+mod history;
+mod stats;
+mod duplicates;
+mod validator;
+mod codicefiscale;
+mod problems;
+mod cardtemplate;
+mod cardprinter;
+mod report;
+mod receipt;
 mod simpleaction;
 mod simpleactionstateful;
 mod liststore;
+mod csvfile;
+mod clipboard;
+mod storage;
+mod csvstorage;
+mod sqlitestorage;
+mod cli;
+mod settings;
+mod peopledialog;
+mod memberdialog;
+mod bulkeditdialog;
+mod findreplacedialog;
+mod duplicatesdialog;
+mod paymentsdialog;
+mod cashsummarydialog;
+mod statsdialog;
+mod signaturedialog;
 mod window;
 mod application;
 
 pub use crate::application::Application;
+pub use crate::cli::run as run_cli;
@@ -6,6 +6,12 @@ use ::gtk::prelude::*;
 
 use crate::column::Column;
 
+// Besides the columns, each row keeps track of the card number data
+// stored outside of the registry, such as the signature, is stored
+// under. It's the number the record had when the document was last
+// saved, and it's not set for records that haven't been saved yet
+pub const KEY: u32 = Column::SIZE as u32;
+
 pub struct ListStore {
     parent: gtk::ListStore,
 }
@@ -13,7 +19,7 @@ pub struct ListStore {
 impl ListStore {
     pub fn new() -> Self {
         Self {
-            parent: gtk::ListStore::new(&[glib::Type::STRING; Column::SIZE]),
+            parent: gtk::ListStore::new(&[glib::Type::STRING; Column::SIZE + 1]),
         }
     }
 
@@ -28,10 +34,18 @@ impl ListStore {
         ]
     }
 
+    pub fn len(&self) -> i32 {
+        self.parent.iter_n_children(None)
+    }
+
     pub fn append(&self) -> gtk::TreeIter {
         self.parent.append()
     }
 
+    pub fn remove(&self, iter: &gtk::TreeIter) {
+        self.parent.remove(iter);
+    }
+
     pub fn value(&self, iter: &gtk::TreeIter, column: &Column) -> Option<String> {
         let variant = self.parent.value(iter, i32::from(column.clone())).get::<String>();
 
@@ -41,6 +55,32 @@ impl ListStore {
         }
     }
 
+    pub fn record(&self, iter: &gtk::TreeIter) -> [String; Column::SIZE] {
+        let mut values = ListStore::new_row();
+
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = self.value(iter, &Column::from(i)).unwrap_or_default();
+        }
+
+        values
+    }
+
+    // Falls back to the current card number
+    pub fn key(&self, iter: &gtk::TreeIter) -> String {
+        match self.parent.value(iter, KEY as i32).get::<String>() {
+            Ok(key) => key,
+            Err(_) => self.value(iter, &Column::Number).unwrap_or_default(),
+        }
+    }
+
+    pub fn set_key(&self, iter: &gtk::TreeIter, key: &str) {
+        let record: [(u32, &dyn glib::ToValue); 1] = [
+            (KEY, &key),
+        ];
+
+        self.parent.set(iter, &record);
+    }
+
     pub fn set_value(&self, iter: &gtk::TreeIter, column: &Column, value: &String) {
         let record: [(u32, &dyn glib::ToValue); 1] = [
             (u32::from(column.clone()), value),
@@ -2,6 +2,14 @@
 // Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
 // SPDX-License-Identifier: GPL-2.0-or-later
 
+use std::process;
+
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+
+    if let Some(status) = tesserama::run_cli(&args) {
+        process::exit(status);
+    }
+
     tesserama::Application::new().run();
 }
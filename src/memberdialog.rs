@@ -0,0 +1,298 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ::gtk::prelude::*;
+
+use crate::barcode;
+use crate::column;
+use crate::column::Column;
+use crate::liststore::ListStore;
+use crate::person::Person;
+use crate::peopledialog::PeopleDialog;
+use crate::signature;
+use crate::signature::Signature;
+use crate::signaturedialog::SignatureDialog;
+
+#[derive(Clone)]
+pub struct MemberDialog {
+    parent: gtk::Dialog,
+    entries: Vec<gtk::Entry>,
+    problems: gtk::Label,
+    signaturearea: gtk::DrawingArea,
+    barcodearea: gtk::DrawingArea,
+    signature: Rc<RefCell<Signature>>,
+    signature_changed: Rc<Cell<bool>>,
+}
+
+impl MemberDialog {
+    pub fn new<W: IsA<gtk::Window>>(window: &W, values: &[String], signature: Option<Signature>) -> Self {
+        let ret = Self {
+            parent: gtk::Dialog::with_buttons(
+                Some("Member details"),
+                Some(window),
+                gtk::DialogFlags::MODAL,
+                &[("Cancel", gtk::ResponseType::Cancel), ("Save", gtk::ResponseType::Ok)],
+            ),
+            entries: (0..Column::SIZE).map(|_| gtk::Entry::new()).collect(),
+            problems: gtk::Label::new(None),
+            signaturearea: gtk::DrawingArea::new(),
+            barcodearea: gtk::DrawingArea::new(),
+            signature: Rc::new(RefCell::new(signature.unwrap_or_else(Signature::new))),
+            signature_changed: Rc::new(Cell::new(false)),
+        };
+        ret.setup(values);
+        ret
+    }
+
+    fn setup(&self, values: &[String]) {
+        self.parent.set_default_response(gtk::ResponseType::Ok);
+        self.parent.set_default_size(500, -1);
+
+        let grid = gtk::Grid::new();
+        grid.set_border_width(12);
+        grid.set_row_spacing(6);
+        grid.set_column_spacing(12);
+
+        // Fields are displayed in the same order they're found in
+        // the treeview, followed by those that are not displayed
+        // there at all
+        let order = [
+            Column::Number,
+            Column::People,
+            Column::Signature,
+            Column::ID,
+            Column::Date,
+            Column::Flags,
+        ];
+
+        for (row, column) in order.iter().enumerate() {
+            let entry = self.entry(column);
+            entry.set_text(&values[usize::from(column.clone())]);
+            entry.set_activates_default(true);
+            entry.set_hexpand(true);
+
+            let _self = self.clone();
+            entry.connect_changed(move |_| {
+                _self.entry_changed();
+            });
+
+            let label = gtk::Label::new(Some(column.title()));
+            label.set_xalign(1.0);
+            label.style_context().add_class("dim-label");
+
+            grid.attach(&label, 0, row as i32, 1, 1);
+            grid.attach(entry, 1, row as i32, 1, 1);
+
+            // People have a dedicated editor, since the format used
+            // to store them is not very friendly
+            if let Column::People = column {
+                let button = gtk::Button::with_label("Edit…");
+                let _self = self.clone();
+                button.connect_clicked(move |_| {
+                    _self.edit_people_clicked();
+                });
+                grid.attach(&button, 2, row as i32, 1, 1);
+            }
+
+            // The Signature column contains the initials of whoever
+            // processed the card, while the actual handwritten
+            // signature is stored separately
+            if let Column::Signature = column {
+                let button = gtk::Button::with_label("Capture…");
+                let _self = self.clone();
+                button.connect_clicked(move |_| {
+                    _self.capture_signature_clicked();
+                });
+                grid.attach(&button, 2, row as i32, 1, 1);
+            }
+        }
+
+        let height = 60;
+        self.signaturearea.set_size_request((height as f64 * signature::ASPECT_RATIO) as i32, height);
+        self.signaturearea.set_halign(gtk::Align::Start);
+        let _self = self.clone();
+        self.signaturearea.connect_draw(move |area, cr| {
+            _self.draw_signature(area, cr)
+        });
+        let frame = gtk::Frame::new(None);
+        frame.set_halign(gtk::Align::Start);
+        frame.add(&self.signaturearea);
+
+        let label = gtk::Label::new(Some("Handwritten"));
+        label.set_xalign(1.0);
+        label.style_context().add_class("dim-label");
+
+        grid.attach(&label, 0, order.len() as i32, 1, 1);
+        grid.attach(&frame, 1, order.len() as i32, 2, 1);
+
+        // The same barcode that's printed on the card, so that it
+        // can be scanned off the screen if the card has been lost
+        self.barcodearea.set_size_request(240, 50);
+        self.barcodearea.set_halign(gtk::Align::Start);
+        let _self = self.clone();
+        self.barcodearea.connect_draw(move |area, cr| {
+            _self.draw_barcode(area, cr)
+        });
+        let frame = gtk::Frame::new(None);
+        frame.set_halign(gtk::Align::Start);
+        frame.add(&self.barcodearea);
+
+        let label = gtk::Label::new(Some("Barcode"));
+        label.set_xalign(1.0);
+        label.style_context().add_class("dim-label");
+
+        grid.attach(&label, 0, order.len() as i32 + 1, 1, 1);
+        grid.attach(&frame, 1, order.len() as i32 + 1, 2, 1);
+
+        self.problems.set_xalign(0.0);
+        self.problems.set_line_wrap(true);
+        self.problems.style_context().add_class("dim-label");
+        grid.attach(&self.problems, 0, order.len() as i32 + 2, 3, 1);
+
+        self.parent.content_area().add(&grid);
+
+        self.validate();
+    }
+
+    fn entry(&self, column: &Column) -> &gtk::Entry {
+        &self.entries[usize::from(column.clone())]
+    }
+
+    // Returns the new signature if it has been captured again, with
+    // an empty one meaning that the existing one should be removed
+    pub fn changed_signature(&self) -> Option<Signature> {
+        if self.signature_changed.get() {
+            Some(self.signature.borrow().clone())
+        } else {
+            None
+        }
+    }
+
+    pub fn values(&self) -> [String; Column::SIZE] {
+        let mut values = ListStore::new_row();
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            values[i] = entry.text().trim().to_string();
+        }
+
+        values
+    }
+
+    // Checks all fields, marking the ones that contain invalid values
+    // and explaining what's wrong with them. Saving is only allowed
+    // once all problems have been addressed
+    fn validate(&self) {
+        let values = self.values();
+        let mut problems = Vec::new();
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            let problem = MemberDialog::check(&Column::from(i), &values[i]);
+            let style = entry.style_context();
+
+            match problem {
+                Some(problem) => {
+                    style.add_class("error");
+                    problems.push(problem);
+                },
+                None => style.remove_class("error"),
+            }
+        }
+
+        self.problems.set_text(&problems.join("\n"));
+        self.parent.set_response_sensitive(gtk::ResponseType::Ok, problems.is_empty());
+    }
+
+    fn check(column: &Column, value: &str) -> Option<&'static str> {
+        match column {
+            Column::Number if value.parse::<i32>().map_or(true, |n| n <= 0) => {
+                Some("The number must be a positive integer.")
+            },
+            Column::People if value.is_empty() => {
+                Some("At least one person must be listed.")
+            },
+            Column::Date if !value.is_empty() && column::parse_date(value).is_none() => {
+                Some("The date must be in the DD/MM/YY format.")
+            },
+            _ => None,
+        }
+    }
+
+    // Returns the edited values, or None if the user has cancelled
+    // the operation
+    pub fn run(&self) -> Option<[String; Column::SIZE]> {
+        self.parent.show_all();
+
+        let ret = match self.parent.run() {
+            gtk::ResponseType::Ok => Some(self.values()),
+            _ => None,
+        };
+
+        unsafe {
+            self.parent.destroy();
+        }
+
+        ret
+    }
+
+    // Signal handlers
+
+    fn entry_changed(&self) {
+        self.validate();
+        self.barcodearea.queue_draw();
+    }
+
+    fn draw_signature(&self, area: &gtk::DrawingArea, cr: &cairo::Context) -> glib::signal::Inhibit {
+        let width = area.allocated_width() as f64;
+        let height = area.allocated_height() as f64;
+
+        cr.set_source_rgb(1.0, 1.0, 1.0);
+        let _ = cr.paint();
+
+        self.signature.borrow().draw(cr, width, height);
+
+        glib::signal::Inhibit(false)
+    }
+
+    fn draw_barcode(&self, area: &gtk::DrawingArea, cr: &cairo::Context) -> glib::signal::Inhibit {
+        let width = area.allocated_width() as f64;
+        let height = area.allocated_height() as f64;
+
+        cr.set_source_rgb(1.0, 1.0, 1.0);
+        let _ = cr.paint();
+
+        // Leave some room around the barcode, which scanners need
+        // in order to find where it starts and ends
+        let margin = 10.0;
+
+        if let Some(code) = barcode::encode(&self.entry(&Column::Number).text()) {
+            cr.translate(margin, margin / 2.0);
+            barcode::draw(cr, &code, width - 2.0 * margin, height - margin);
+        }
+
+        glib::signal::Inhibit(false)
+    }
+
+    fn capture_signature_clicked(&self) {
+        let dialog = SignatureDialog::new(&self.parent);
+
+        if let Some(signature) = dialog.run() {
+            *self.signature.borrow_mut() = signature;
+            self.signature_changed.set(true);
+            self.signaturearea.queue_draw();
+        }
+    }
+
+    fn edit_people_clicked(&self) {
+        let entry = self.entry(&Column::People);
+        let dialog = PeopleDialog::new(&self.parent, &Person::parse_list(&entry.text()));
+
+        if let Some(people) = dialog.run() {
+            entry.set_text(&Person::format_list(&people));
+        }
+    }
+}
@@ -0,0 +1,534 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::cmp;
+
+use chrono::Datelike;
+
+use crate::barcode;
+use crate::column;
+use crate::column::Column;
+
+#[derive(Clone)]
+pub enum Filter {
+    Empty(Column),
+    Contains(Column, String),
+    Year(i32),
+}
+
+impl Filter {
+    pub fn column(&self) -> Column {
+        match self {
+            Filter::Empty(column) => column.clone(),
+            Filter::Contains(column, _) => column.clone(),
+            Filter::Year(_) => Column::Date,
+        }
+    }
+
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            Filter::Empty(_) => value.trim().is_empty(),
+            Filter::Contains(_, needle) => fold(value).contains(needle.as_str()),
+            Filter::Year(year) => {
+                column::parse_date(value).map_or(false, |date| date.year() == *year)
+            },
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Needle {
+    text: String,
+    chunks: Vec<String>,
+    filters: Vec<Filter>,
+    fuzzy: bool,
+}
+
+impl Needle {
+    pub fn new(text: &str, fuzzy: bool) -> Self {
+        let text = String::from(text.trim());
+        let mut chunks = Vec::new();
+        let mut filters = Vec::new();
+
+        // Chunks in the form "column:value" restrict the search to
+        // records whose column contains value, or is empty if no
+        // value has been provided. "year:value" is handled specially
+        // and matches the year the card was issued in, with "this"
+        // standing for the current year. Everything else is free
+        // text that will be looked up in names and signatures
+        for chunk in text.split_whitespace() {
+            match Needle::parse_filter(chunk) {
+                Some(filter) => filters.push(filter),
+                None => chunks.extend(fold(chunk).split_whitespace().map(String::from)),
+            }
+        }
+
+        Self {
+            text,
+            chunks,
+            filters,
+            fuzzy,
+        }
+    }
+
+    fn parse_filter(chunk: &str) -> Option<Filter> {
+        let (name, value) = chunk.split_at(chunk.find(':')?);
+        let value = &value[1..];
+
+        if name.eq_ignore_ascii_case("year") {
+            return match value {
+                "this" => Some(Filter::Year(chrono::Local::today().year())),
+                _ => value.parse::<i32>().ok().map(Filter::Year),
+            };
+        }
+
+        let column = Column::from_name(&name.to_lowercase())?;
+
+        if value.is_empty() {
+            Some(Filter::Empty(column))
+        } else {
+            Some(Filter::Contains(column, fold(value)))
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty() && self.filters.is_empty()
+    }
+
+    pub fn is_fuzzy(&self) -> bool {
+        self.fuzzy
+    }
+
+    pub fn filters(&self) -> &[Filter] {
+        &self.filters
+    }
+
+    // Scanned barcodes are treated the same as card numbers typed
+    // in by hand
+    pub fn as_number(&self) -> Option<&str> {
+        match self.text.parse::<i32>() {
+            Ok(_) => Some(&self.text),
+            Err(_) => barcode::decode(&self.text),
+        }
+    }
+
+    // Returns None if the record doesn't match, or a score otherwise.
+    // Lower scores are better
+    pub fn score_record(&self, record: &[String]) -> Option<usize> {
+        // Restrictions on specific columns have to be satisfied
+        // before we even consider the rest of the needle
+        for filter in &self.filters {
+            if !filter.matches(&record[usize::from(filter.column())]) {
+                return None;
+            }
+        }
+
+        if let Some(number) = self.as_number() {
+            // If the needle can be converted to a number, we look up
+            // the corresponding record
+            if record[usize::from(Column::Number)] == number {
+                Some(0)
+            } else {
+                None
+            }
+        } else {
+            // In all other cases, we perform a case-, accent- and
+            // punctuation-insensitive search among people's names
+            // and signatures.
+            //
+            // Most entries are in the form
+            //
+            //   LastName FirstName, OtherFirstName
+            //
+            // to save on typing.
+            //
+            // We want such an entry to match when searching for
+            // "LastName OtherFirstName", and in order to do that we
+            // have to split the needle into chunks and check whether
+            // all of them are contained in the entry
+            let people = self.score(&record[usize::from(Column::People)]);
+            let signature = self.score(&record[usize::from(Column::Signature)]);
+
+            people.into_iter().chain(signature).min()
+        }
+    }
+
+    // Returns None if the value doesn't match, or a score otherwise.
+    // Lower scores are better, with zero meaning that every chunk
+    // was found verbatim (once folded) in the value
+    pub fn score(&self, value: &str) -> Option<usize> {
+        let value = fold(value);
+        let words: Vec<&str> = value.split_whitespace().collect();
+        let mut total = 0;
+
+        for chunk in &self.chunks {
+            if value.contains(chunk.as_str()) {
+                continue;
+            }
+            if !self.fuzzy {
+                return None;
+            }
+
+            let allowed = allowed_typos(chunk);
+            if allowed == 0 {
+                return None;
+            }
+
+            let chunk: Vec<char> = chunk.chars().collect();
+            let best = words.iter().map(|word| {
+                word_distance(&chunk, &word.chars().collect::<Vec<char>>())
+            }).min();
+
+            match best {
+                Some(distance) if distance <= allowed => total += distance,
+                _ => return None,
+            }
+        }
+
+        Some(total)
+    }
+
+    // Returns Pango markup for the value, with the parts matching
+    // the needle highlighted. Everything else is escaped, so that
+    // the result is safe to use no matter what the value contains
+    pub fn highlight(&self, value: &str) -> String {
+        let original: Vec<char> = value.chars().collect();
+        let (folded, offsets) = fold_with_offsets(value);
+        let folded: Vec<char> = folded.chars().collect();
+
+        let mut marked = vec![false; original.len()];
+        let mut mark = |start: usize, end: usize| {
+            for offset in &offsets[start..end] {
+                marked[*offset] = true;
+            }
+        };
+
+        for chunk in &self.chunks {
+            let chunk: Vec<char> = chunk.chars().collect();
+            let mut found = false;
+
+            if chunk.len() <= folded.len() {
+                for start in 0..=(folded.len() - chunk.len()) {
+                    if folded[start..(start + chunk.len())] == chunk[..] {
+                        mark(start, start + chunk.len());
+                        found = true;
+                    }
+                }
+            }
+
+            // Fuzzy matches are highlighted by marking the whole
+            // word that was considered close enough
+            if !found && self.fuzzy {
+                let allowed = allowed_typos(&chunk.iter().collect::<String>());
+
+                let best = words(&folded).into_iter().map(|(start, end)| {
+                    (word_distance(&chunk, &folded[start..end]), start, end)
+                }).min();
+
+                if let Some((distance, start, end)) = best {
+                    if distance <= allowed {
+                        mark(start, end);
+                    }
+                }
+            }
+        }
+
+        let mut ret = String::new();
+        let mut i = 0;
+
+        while i < original.len() {
+            let highlighted = marked[i];
+            let run: String = original[i..].iter()
+                              .zip(&marked[i..])
+                              .take_while(|(_, m)| **m == highlighted)
+                              .map(|(c, _)| c)
+                              .collect();
+
+            i += run.chars().count();
+
+            if highlighted {
+                ret.push_str("<b>");
+                ret.push_str(&glib::markup_escape_text(&run));
+                ret.push_str("</b>");
+            } else {
+                ret.push_str(&glib::markup_escape_text(&run));
+            }
+        }
+
+        ret
+    }
+}
+
+// Allow roughly one typo every four characters, but never more
+// than two: short chunks are way too likely to match random words
+// otherwise
+fn allowed_typos(chunk: &str) -> usize {
+    cmp::min(chunk.chars().count() / 4, 2)
+}
+
+// Returns the start and end positions of all words
+fn words(text: &[char]) -> Vec<(usize, usize)> {
+    let mut ret = Vec::new();
+    let mut start = None;
+
+    for (i, c) in text.iter().enumerate() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(i),
+            (true, Some(s)) => {
+                ret.push((s, i));
+                start = None;
+            },
+            _ => {},
+        }
+    }
+    if let Some(s) = start {
+        ret.push((s, text.len()));
+    }
+
+    ret
+}
+
+// Folds a string so that comparisons ignore case, diacritics and
+// punctuation. This way "nicolo" will match "Nicolò", and both
+// "damico" and "d amico" will match "D'Amico"
+pub fn fold(text: &str) -> String {
+    fold_with_offsets(text).0
+}
+
+// Same as fold(), but also returns, for each character in the
+// folded string, the position of the one it originated from
+fn fold_with_offsets(text: &str) -> (String, Vec<usize>) {
+    let mut ret = String::with_capacity(text.len());
+    let mut offsets = Vec::with_capacity(text.len());
+
+    for (offset, original) in text.chars().enumerate() {
+        for c in original.to_lowercase() {
+            // Combining diacritical marks, found in decomposed strings
+            if ('\u{0300}'..='\u{036f}').contains(&c) {
+                continue;
+            }
+
+            let folded: String = match fold_char(c) {
+                Some(folded) => String::from(folded),
+                None if c.is_alphanumeric() || c.is_whitespace() => c.to_string(),
+                None if c == ',' || c == '-' || c == '/' => String::from(" "),
+                None => String::new(),
+            };
+
+            for c in folded.chars() {
+                ret.push(c);
+                offsets.push(offset);
+            }
+        }
+    }
+
+    (ret, offsets)
+}
+
+fn fold_char(c: char) -> Option<&'static str> {
+    let folded = match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ď' | 'đ' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ł' | 'ľ' | 'ĺ' | 'ļ' => "l",
+        'ñ' | 'ń' | 'ň' | 'ņ' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'œ' => "oe",
+        'ř' | 'ŕ' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' => "s",
+        'ß' => "ss",
+        'ţ' | 'ť' => "t",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'ý' | 'ÿ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    };
+
+    Some(folded)
+}
+
+// Edit distance between a chunk and a word. The chunk is also
+// compared against a prefix of the word, so that partially typed
+// names still match
+fn word_distance(chunk: &[char], word: &[char]) -> usize {
+    let full = distance(chunk, word);
+    if word.len() > chunk.len() {
+        cmp::min(full, distance(chunk, &word[..chunk.len()]))
+    } else {
+        full
+    }
+}
+
+// Levenshtein distance, computed one row at a time
+fn distance(a: &[char], b: &[char]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.iter().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            let next = cmp::min(cmp::min(row[j + 1] + 1, row[j] + 1), prev + cost);
+            prev = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(text: &str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    fn record(number: &str, people: &str, date: &str) -> Vec<String> {
+        let mut ret = vec![String::new(); Column::SIZE];
+        ret[usize::from(Column::Date)] = String::from(date);
+        ret[usize::from(Column::Number)] = String::from(number);
+        ret[usize::from(Column::People)] = String::from(people);
+        ret
+    }
+
+    #[test]
+    fn fold_accents() {
+        assert_eq!(fold("Nicolò"), "nicolo");
+        assert_eq!(fold("NICOLÒ"), "nicolo");
+        // Decomposed, with a combining grave accent
+        assert_eq!(fold("Nicolo\u{0300}"), "nicolo");
+        assert_eq!(fold("Straße"), "strasse");
+    }
+
+    #[test]
+    fn fold_punctuation() {
+        assert_eq!(fold("D'Amico"), "damico");
+        assert_eq!(fold("De' Medici"), "de medici");
+        assert_eq!(fold("Rossi Mario, Anna"), "rossi mario  anna");
+        assert_eq!(fold("Bianchi-Verdi"), "bianchi verdi");
+    }
+
+    #[test]
+    fn fold_offsets() {
+        let (folded, offsets) = fold_with_offsets("D'Amicò");
+
+        assert_eq!(folded, "damico");
+        assert_eq!(offsets, vec![0, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn levenshtein() {
+        assert_eq!(distance(&chars("kitten"), &chars("sitting")), 3);
+        assert_eq!(distance(&chars(""), &chars("abc")), 3);
+        assert_eq!(distance(&chars("rossi"), &chars("rossi")), 0);
+    }
+
+    #[test]
+    fn word_prefix() {
+        assert_eq!(word_distance(&chars("mar"), &chars("mario")), 0);
+        assert_eq!(word_distance(&chars("mxr"), &chars("mario")), 1);
+        // Transpositions count as two typos
+        assert_eq!(word_distance(&chars("mra"), &chars("mario")), 2);
+        assert_eq!(word_distance(&chars("mariorossi"), &chars("mario")), 5);
+    }
+
+    #[test]
+    fn typo_limit() {
+        assert_eq!(allowed_typos("abc"), 0);
+        assert_eq!(allowed_typos("rosi"), 1);
+        assert_eq!(allowed_typos("bianchi"), 1);
+        assert_eq!(allowed_typos("bianchis"), 2);
+        assert_eq!(allowed_typos("giovannibattista"), 2);
+    }
+
+    #[test]
+    fn score() {
+        let exact = Needle::new("nicolo damico", false);
+        assert_eq!(exact.score("D'Amico Nicolò"), Some(0));
+        assert_eq!(exact.score("D'Amico Nicola"), None);
+
+        // Both chunks are found in "damico"
+        assert_eq!(Needle::new("d amico", false).score("D'Amico"), Some(0));
+        assert_eq!(Needle::new("medici", false).score("De' Medici Lorenzo"), Some(0));
+    }
+
+    #[test]
+    fn score_fuzzy() {
+        assert_eq!(Needle::new("rosi", false).score("Rossi Mario"), None);
+        assert_eq!(Needle::new("rosi", true).score("Rossi Mario"), Some(1));
+        assert_eq!(Needle::new("rosi maro", true).score("Rossi Mario"), Some(2));
+
+        // Too short for any typo to be allowed
+        assert_eq!(Needle::new("rsi", true).score("Rossi Mario"), None);
+        // Two typos, but only one is allowed
+        assert_eq!(Needle::new("rxsx", true).score("Rossi Mario"), None);
+    }
+
+    #[test]
+    fn filters() {
+        let year = chrono::Local::today().year();
+
+        assert!(matches!(Needle::parse_filter("year:this"), Some(Filter::Year(y)) if y == year));
+        assert!(matches!(Needle::parse_filter("YEAR:2021"), Some(Filter::Year(2021))));
+        assert!(Needle::parse_filter("year:last").is_none());
+        assert!(matches!(Needle::parse_filter("people:"), Some(Filter::Empty(Column::People))));
+        assert!(matches!(
+            Needle::parse_filter("People:Rossì"),
+            Some(Filter::Contains(Column::People, ref value)) if value == "rossi"
+        ));
+        assert!(Needle::parse_filter("colour:red").is_none());
+        assert!(Needle::parse_filter("rossi").is_none());
+    }
+
+    #[test]
+    fn filter_matches() {
+        assert!(Filter::Empty(Column::ID).matches("  "));
+        assert!(!Filter::Empty(Column::ID).matches("X"));
+        assert!(Filter::Contains(Column::People, String::from("rossi")).matches("Rossì Mario"));
+        assert!(Filter::Year(2021).matches("10/01/21"));
+        assert!(!Filter::Year(2021).matches("10/01/20"));
+        assert!(!Filter::Year(2021).matches(""));
+    }
+
+    #[test]
+    fn as_number() {
+        assert_eq!(Needle::new("42", false).as_number(), Some("42"));
+        assert_eq!(Needle::new(" 42 ", false).as_number(), Some("42"));
+
+        let code = barcode::encode("42").unwrap();
+        assert_eq!(Needle::new(&code, false).as_number(), Some("42"));
+        assert_eq!(Needle::new(&code.to_lowercase(), false).as_number(), Some("42"));
+
+        assert_eq!(Needle::new("TS421", false).as_number(), None);
+        assert_eq!(Needle::new("rossi", false).as_number(), None);
+    }
+
+    #[test]
+    fn score_record() {
+        let rossi = record("42", "Rossi Mario", "10/01/21");
+        let bianchi = record("7", "Bianchi Anna", "10/01/20");
+
+        let number = Needle::new("42", false);
+        assert_eq!(number.score_record(&rossi), Some(0));
+        assert_eq!(number.score_record(&bianchi), None);
+
+        let name = Needle::new("mario", false);
+        assert_eq!(name.score_record(&rossi), Some(0));
+        assert_eq!(name.score_record(&bianchi), None);
+
+        let filtered = Needle::new("year:2020 anna", false);
+        assert_eq!(filtered.score_record(&rossi), None);
+        assert_eq!(filtered.score_record(&bianchi), Some(0));
+
+        let empty = Needle::new("id:", false);
+        assert_eq!(empty.score_record(&rossi), Some(0));
+    }
+}
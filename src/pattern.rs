@@ -0,0 +1,112 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+// What to look for when replacing text in the registry. Plain text
+// is turned into a regular expression too, so that all options work
+// the same way regardless of how the pattern has been provided
+pub struct Pattern {
+    regex: regex::Regex,
+    replacement: String,
+    expand: bool,
+}
+
+impl Pattern {
+    // When using regular expressions, the replacement can refer to
+    // capture groups as $1, $2 and so on
+    pub fn new(find: &str, replacement: &str, case_sensitive: bool, whole_word: bool, use_regex: bool) -> Result<Self, regex::Error> {
+        let mut pattern = if use_regex {
+            String::from(find)
+        } else {
+            regex::escape(find)
+        };
+
+        if whole_word {
+            pattern = format!(r"\b(?:{})\b", pattern);
+        }
+
+        let regex = regex::RegexBuilder::new(&pattern)
+                    .case_insensitive(!case_sensitive)
+                    .build()?;
+
+        Ok(Self {
+            regex,
+            replacement: String::from(replacement),
+            expand: use_regex,
+        })
+    }
+
+    pub fn is_match(&self, value: &str) -> bool {
+        self.regex.is_match(value)
+    }
+
+    pub fn replace(&self, value: &str) -> String {
+        if self.expand {
+            self.regex.replace_all(value, self.replacement.as_str()).to_string()
+        } else {
+            self.regex.replace_all(value, regex::NoExpand(&self.replacement)).to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replace(find: &str, replacement: &str, case_sensitive: bool, whole_word: bool, use_regex: bool, value: &str) -> String {
+        Pattern::new(find, replacement, case_sensitive, whole_word, use_regex).unwrap().replace(value)
+    }
+
+    #[test]
+    fn plain_text() {
+        assert_eq!(replace("Rossi", "Russo", true, false, false, "Rossi Mario; Rossini Anna"), "Russo Mario; Russoni Anna");
+        // Special characters are taken literally
+        assert_eq!(replace("a.b", "x", true, false, false, "a.b acb"), "x acb");
+        assert_eq!(replace("(1)", "1", true, false, false, "Rossi (1)"), "Rossi 1");
+    }
+
+    #[test]
+    fn plain_text_replacement() {
+        // Only regular expressions can refer to capture groups
+        assert_eq!(replace("5", "$1", true, false, false, "15/05"), "1$1/0$1");
+        assert_eq!(replace("euro", "${amount}", true, false, false, "10 euro"), "10 ${amount}");
+    }
+
+    #[test]
+    fn case() {
+        assert_eq!(replace("rossi", "Russo", false, false, false, "ROSSI Mario"), "Russo Mario");
+        assert_eq!(replace("rossi", "Russo", true, false, false, "ROSSI Mario"), "ROSSI Mario");
+        assert_eq!(replace("rossì", "Russo", false, false, false, "ROSSÌ Mario"), "Russo Mario");
+    }
+
+    #[test]
+    fn whole_word() {
+        assert_eq!(replace("Rossi", "Russo", true, true, false, "Rossi Mario; Rossini Anna"), "Russo Mario; Rossini Anna");
+        assert_eq!(replace("De", "Di", false, true, false, "de Luca, Dede"), "Di Luca, Dede");
+        // Alternatives are all limited to whole words
+        assert_eq!(replace("an|ma", "x", true, true, true, "an ant ma man"), "x ant x man");
+    }
+
+    #[test]
+    fn regex() {
+        assert_eq!(
+            replace(r"(\d+)-(\d+)-(\d+)", "$1/$2/$3", true, false, true, "10-01-21"),
+            "10/01/21",
+        );
+        assert_eq!(replace(r"^\s+|\s+$", "", true, false, true, "  Rossi  "), "Rossi");
+    }
+
+    #[test]
+    fn matches() {
+        let pattern = Pattern::new("mario", "", false, true, false).unwrap();
+
+        assert!(pattern.is_match("Rossi Mario"));
+        assert!(!pattern.is_match("Rossi Marione"));
+    }
+
+    #[test]
+    fn invalid_regex() {
+        assert!(Pattern::new("(", "", true, false, true).is_err());
+        assert!(Pattern::new("(", "", true, false, false).is_ok());
+    }
+}
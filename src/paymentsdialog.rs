@@ -0,0 +1,305 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ::gtk::prelude::*;
+
+use crate::column;
+use crate::ledger;
+use crate::ledger::Payment;
+
+#[derive(Clone)]
+struct Row {
+    parent: gtk::Box,
+    // Payments made for earlier cards keep pointing to them
+    number: String,
+    id: String,
+    date: gtk::Entry,
+    amount: gtk::Entry,
+    method: gtk::ComboBoxText,
+    receipt: gtk::Entry,
+    operator: gtk::Entry,
+}
+
+#[derive(Clone)]
+pub struct PaymentsDialog {
+    parent: gtk::Dialog,
+    number: String,
+    id: String,
+    fee: i64,
+    list: gtk::Box,
+    rows: Rc<RefCell<Vec<Row>>>,
+    receipt_requested: Rc<Cell<Option<usize>>>,
+    valid: Rc<Cell<bool>>,
+}
+
+impl PaymentsDialog {
+    pub fn new<W: IsA<gtk::Window>>(window: &W, number: &str, id: &str, people: &str, payments: &[Payment], fee: i64) -> Self {
+        let ret = Self {
+            parent: gtk::Dialog::with_buttons(
+                Some(&format!("Payments for N. {}", number)),
+                Some(window),
+                gtk::DialogFlags::MODAL,
+                &[("Cancel", gtk::ResponseType::Cancel), ("Save", gtk::ResponseType::Ok)],
+            ),
+            number: String::from(number),
+            id: String::from(id),
+            fee,
+            list: gtk::Box::new(gtk::Orientation::Vertical, 6),
+            rows: Rc::new(RefCell::new(Vec::new())),
+            receipt_requested: Rc::new(Cell::new(None)),
+            valid: Rc::new(Cell::new(true)),
+        };
+        ret.setup(people, payments);
+        ret
+    }
+
+    fn setup(&self, people: &str, payments: &[Payment]) {
+        self.parent.set_default_response(gtk::ResponseType::Ok);
+        self.parent.set_default_size(700, -1);
+
+        let title = gtk::Label::new(Some(people));
+        title.set_xalign(0.0);
+        title.set_ellipsize(pango::EllipsizeMode::End);
+
+        let header = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        for text in &["Date", "Amount", "Method", "Receipt", "Operator"] {
+            let label = gtk::Label::new(Some(text));
+            label.set_xalign(0.0);
+            label.style_context().add_class("dim-label");
+            header.pack_start(&label, true, true, 0);
+        }
+        // Make room for the buttons
+        let spacer = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        spacer.set_size_request(74, -1);
+        header.pack_start(&spacer, false, false, 0);
+
+        let addbutton = gtk::Button::with_label("Add payment");
+        addbutton.set_halign(gtk::Align::Start);
+        let _self = self.clone();
+        addbutton.connect_clicked(move |_| {
+            _self.add_clicked();
+        });
+
+        let contents = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        contents.set_border_width(12);
+        contents.pack_start(&title, false, false, 0);
+        contents.pack_start(&header, false, false, 0);
+        contents.pack_start(&self.list, false, false, 0);
+        contents.pack_start(&addbutton, false, false, 0);
+        self.parent.content_area().add(&contents);
+
+        for payment in payments {
+            self.add_row(payment);
+        }
+
+        self.validate();
+    }
+
+    // A new payment is prefilled with today's date and the fee, which
+    // is by far the most common case
+    fn new_payment(&self) -> Payment {
+        Payment {
+            number: self.number.clone(),
+            id: self.id.clone(),
+            date: chrono::Local::today().format("%d/%m/%y").to_string(),
+            amount: self.fee,
+            method: String::from(ledger::METHODS[0]),
+            receipt: String::new(),
+            operator: String::new(),
+        }
+    }
+
+    fn add_row(&self, payment: &Payment) {
+        let entry = |text: &str| {
+            let entry = gtk::Entry::new();
+            entry.set_text(text);
+            entry.set_width_chars(8);
+            entry.set_activates_default(true);
+
+            let _self = self.clone();
+            entry.connect_changed(move |_| {
+                _self.entry_changed();
+            });
+
+            entry
+        };
+
+        let method = gtk::ComboBoxText::with_entry();
+        for m in &ledger::METHODS {
+            method.append_text(m);
+        }
+        if let Some(child) = method.child().and_then(|c| c.downcast::<gtk::Entry>().ok()) {
+            child.set_text(&payment.method);
+            child.set_width_chars(8);
+        }
+
+        let row = Row {
+            parent: gtk::Box::new(gtk::Orientation::Horizontal, 6),
+            number: payment.number.clone(),
+            id: payment.id.clone(),
+            date: entry(&payment.date),
+            amount: entry(&ledger::format_amount(payment.amount)),
+            method,
+            receipt: entry(&payment.receipt),
+            operator: entry(&payment.operator),
+        };
+
+        let receiptbutton = gtk::Button::from_icon_name(
+            Some("document-print-symbolic"),
+            gtk::IconSize::Button,
+        );
+        receiptbutton.set_tooltip_text(Some("Receipt"));
+
+        let removebutton = gtk::Button::from_icon_name(
+            Some("list-remove-symbolic"),
+            gtk::IconSize::Button,
+        );
+        removebutton.set_tooltip_text(Some("Remove"));
+
+        // Receipt numbers are only ever assigned when issuing a receipt
+        row.receipt.set_editable(false);
+        row.receipt.set_can_focus(false);
+
+        // Once a receipt has been issued the payment can no longer be
+        // changed or removed, otherwise the receipt would not match it
+        // anymore and there would be a gap in the numbering
+        if !payment.receipt.is_empty() {
+            for entry in &[&row.date, &row.amount, &row.operator] {
+                entry.set_editable(false);
+            }
+            row.method.set_sensitive(false);
+            removebutton.set_sensitive(false);
+            removebutton.set_tooltip_text(Some("Payments with a receipt can't be removed"));
+        }
+
+        row.parent.pack_start(&row.date, true, true, 0);
+        row.parent.pack_start(&row.amount, true, true, 0);
+        row.parent.pack_start(&row.method, true, true, 0);
+        row.parent.pack_start(&row.receipt, true, true, 0);
+        row.parent.pack_start(&row.operator, true, true, 0);
+        row.parent.pack_start(&receiptbutton, false, false, 0);
+        row.parent.pack_start(&removebutton, false, false, 0);
+
+        let _self = self.clone();
+        let _row = row.parent.clone();
+        receiptbutton.connect_clicked(move |_| {
+            _self.receipt_clicked(&_row);
+        });
+
+        let _self = self.clone();
+        let _row = row.parent.clone();
+        removebutton.connect_clicked(move |_| {
+            _self.remove_clicked(&_row);
+        });
+
+        self.list.pack_start(&row.parent, false, false, 0);
+        row.parent.show_all();
+
+        self.rows.borrow_mut().push(row);
+    }
+
+    // Marks dates and amounts that can't be parsed; saving is only
+    // allowed once they've all been fixed
+    fn validate(&self) {
+        let mut valid = true;
+
+        for row in self.rows.borrow().iter() {
+            let checks = [
+                (&row.date, column::parse_date(&row.date.text()).is_some()),
+                (&row.amount, ledger::parse_amount(&row.amount.text()).is_some()),
+            ];
+
+            for (entry, ok) in &checks {
+                if *ok {
+                    entry.style_context().remove_class("error");
+                } else {
+                    entry.style_context().add_class("error");
+                    valid = false;
+                }
+            }
+        }
+
+        self.valid.set(valid);
+        self.parent.set_response_sensitive(gtk::ResponseType::Ok, valid);
+    }
+
+    // Returns the position, among the returned payments, of the one
+    // the user has asked a receipt for
+    pub fn receipt_requested(&self) -> Option<usize> {
+        self.receipt_requested.get()
+    }
+
+    pub fn payments(&self) -> Vec<Payment> {
+        self.rows.borrow().iter().filter_map(|row| {
+            Some(Payment {
+                number: row.number.clone(),
+                id: row.id.clone(),
+                date: row.date.text().trim().to_string(),
+                amount: ledger::parse_amount(&row.amount.text())?,
+                method: row.method.active_text().map_or(String::new(), |m| m.trim().to_string()),
+                receipt: row.receipt.text().trim().to_string(),
+                operator: row.operator.text().trim().to_string(),
+            })
+        }).collect()
+    }
+
+    // Returns the edited list of payments, or None if the user has
+    // cancelled the operation
+    pub fn run(&self) -> Option<Vec<Payment>> {
+        self.parent.show_all();
+
+        let ret = match self.parent.run() {
+            gtk::ResponseType::Ok => Some(self.payments()),
+            _ => None,
+        };
+
+        unsafe {
+            self.parent.destroy();
+        }
+
+        ret
+    }
+
+    // Signal handlers
+
+    fn entry_changed(&self) {
+        self.validate();
+    }
+
+    fn add_clicked(&self) {
+        self.add_row(&self.new_payment());
+        self.validate();
+    }
+
+    // Receipts are issued for saved payments only, so asking for one
+    // saves the changes and closes the dialog
+    fn receipt_clicked(&self, row: &gtk::Box) {
+        if !self.valid.get() {
+            return;
+        }
+
+        let index = self.rows.borrow().iter().position(|r| &r.parent == row);
+
+        self.receipt_requested.set(index);
+        self.parent.response(gtk::ResponseType::Ok);
+    }
+
+    fn remove_clicked(&self, row: &gtk::Box) {
+        let has_receipt = self.rows.borrow().iter().any(|r| {
+            &r.parent == row && !r.receipt.text().is_empty()
+        });
+
+        if has_receipt {
+            return;
+        }
+
+        self.list.remove(row);
+        self.rows.borrow_mut().retain(|r| &r.parent != row);
+        self.validate();
+    }
+}
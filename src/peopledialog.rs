@@ -0,0 +1,136 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ::gtk::prelude::*;
+
+use crate::person::Person;
+
+#[derive(Clone)]
+pub struct PeopleDialog {
+    parent: gtk::Dialog,
+    list: gtk::Box,
+    rows: Rc<RefCell<Vec<(gtk::Box, gtk::Entry, gtk::Entry)>>>,
+}
+
+impl PeopleDialog {
+    pub fn new<W: IsA<gtk::Window>>(window: &W, people: &[Person]) -> Self {
+        let ret = Self {
+            parent: gtk::Dialog::with_buttons(
+                Some("Edit people"),
+                Some(window),
+                gtk::DialogFlags::MODAL,
+                &[("Cancel", gtk::ResponseType::Cancel), ("Save", gtk::ResponseType::Ok)],
+            ),
+            list: gtk::Box::new(gtk::Orientation::Vertical, 6),
+            rows: Rc::new(RefCell::new(Vec::new())),
+        };
+        ret.setup(people);
+        ret
+    }
+
+    fn setup(&self, people: &[Person]) {
+        self.parent.set_default_response(gtk::ResponseType::Ok);
+        self.parent.set_default_size(500, -1);
+
+        let header = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        let surname_label = gtk::Label::new(Some("Surname"));
+        surname_label.set_xalign(0.0);
+        let given_names_label = gtk::Label::new(Some("Given names"));
+        given_names_label.set_xalign(0.0);
+        header.pack_start(&surname_label, true, true, 0);
+        header.pack_start(&given_names_label, true, true, 0);
+
+        let addbutton = gtk::Button::with_label("Add person");
+        addbutton.set_halign(gtk::Align::Start);
+        let _self = self.clone();
+        addbutton.connect_clicked(move |_| {
+            _self.add_clicked();
+        });
+
+        let contents = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        contents.set_border_width(12);
+        contents.pack_start(&header, false, false, 0);
+        contents.pack_start(&self.list, false, false, 0);
+        contents.pack_start(&addbutton, false, false, 0);
+        self.parent.content_area().add(&contents);
+
+        for person in people {
+            self.add_row(person);
+        }
+
+        // Always offer at least one row to fill in
+        if people.is_empty() {
+            self.add_row(&Person::new("", ""));
+        }
+    }
+
+    fn add_row(&self, person: &Person) {
+        let surname = gtk::Entry::new();
+        surname.set_text(&person.surname);
+        surname.set_activates_default(true);
+
+        let given_names = gtk::Entry::new();
+        given_names.set_text(&person.given_names);
+        given_names.set_activates_default(true);
+
+        let removebutton = gtk::Button::from_icon_name(
+            Some("list-remove-symbolic"),
+            gtk::IconSize::Button,
+        );
+        removebutton.set_tooltip_text(Some("Remove"));
+
+        let row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        row.pack_start(&surname, true, true, 0);
+        row.pack_start(&given_names, true, true, 0);
+        row.pack_start(&removebutton, false, false, 0);
+
+        let _self = self.clone();
+        let _row = row.clone();
+        removebutton.connect_clicked(move |_| {
+            _self.remove_clicked(&_row);
+        });
+
+        self.list.pack_start(&row, false, false, 0);
+        row.show_all();
+
+        self.rows.borrow_mut().push((row, surname, given_names));
+    }
+
+    pub fn people(&self) -> Vec<Person> {
+        self.rows.borrow().iter().map(|(_, surname, given_names)| {
+            Person::new(&surname.text(), &given_names.text())
+        }).filter(|person| !person.is_empty()).collect()
+    }
+
+    // Returns the edited list of people, or None if the user has
+    // cancelled the operation
+    pub fn run(&self) -> Option<Vec<Person>> {
+        self.parent.show_all();
+
+        let ret = match self.parent.run() {
+            gtk::ResponseType::Ok => Some(self.people()),
+            _ => None,
+        };
+
+        unsafe {
+            self.parent.destroy();
+        }
+
+        ret
+    }
+
+    // Signal handlers
+
+    fn add_clicked(&self) {
+        self.add_row(&Person::new("", ""));
+    }
+
+    fn remove_clicked(&self, row: &gtk::Box) {
+        self.list.remove(row);
+        self.rows.borrow_mut().retain(|(r, _, _)| r != row);
+    }
+}
@@ -0,0 +1,210 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+// Words that are part of a surname rather than a standalone one
+const PARTICLES: [&str; 16] = [
+    "d'", "da", "dal", "dalla", "de", "de'", "degli", "dei",
+    "del", "della", "di", "la", "lo", "san", "van", "von",
+];
+
+#[derive(Clone)]
+pub struct Person {
+    pub surname: String,
+    pub given_names: String,
+}
+
+impl Person {
+    pub fn new(surname: &str, given_names: &str) -> Self {
+        Self {
+            surname: normalize_spaces(surname),
+            given_names: normalize_spaces(given_names),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.surname.is_empty() && self.given_names.is_empty()
+    }
+
+    // Parses the contents of the People column. Most entries are in
+    // the form
+    //
+    //   LastName FirstName, OtherFirstName
+    //
+    // which describes two people sharing the same last name. Several
+    // families can share a single card, in which case they are
+    // separated by semicolons
+    //
+    //   LastName FirstName, OtherFirstName; OtherLastName FirstName
+    //
+    // Since the last name comes first and is not otherwise marked,
+    // only particles such as "De" or "Della" are recognized as being
+    // part of it: anything more exotic needs to be fixed by hand
+    pub fn parse_list(text: &str) -> Vec<Person> {
+        let mut ret = Vec::new();
+
+        for family in text.split(';') {
+            let mut surname = String::new();
+
+            for (i, part) in family.split(',').enumerate() {
+                let words: Vec<&str> = part.split_whitespace().collect();
+
+                if words.is_empty() {
+                    continue;
+                }
+
+                if i == 0 {
+                    let length = surname_length(&words);
+                    surname = words[..length].join(" ");
+                    ret.push(Person::new(&surname, &words[length..].join(" ")));
+                } else {
+                    ret.push(Person::new(&surname, &words.join(" ")));
+                }
+            }
+        }
+
+        ret
+    }
+
+    // The opposite of parse_list(): people sharing the same last name
+    // are grouped together, same as when entering data by hand
+    pub fn format_list(people: &[Person]) -> String {
+        let mut families: Vec<(String, Vec<String>)> = Vec::new();
+
+        for person in people.iter().filter(|p| !p.is_empty()) {
+            match families.last_mut() {
+                Some((surname, given_names)) if *surname == person.surname => {
+                    given_names.push(person.given_names.clone());
+                },
+                _ => {
+                    families.push((person.surname.clone(), vec![person.given_names.clone()]));
+                },
+            }
+        }
+
+        families.iter().map(|(surname, given_names)| {
+            // Without a surname, the first given name would be taken
+            // for one when parsing, so it goes after the separator
+            let mut parts = if surname.is_empty() {
+                vec![String::new(), given_names[0].clone()]
+            } else {
+                vec![String::from(format!("{} {}", surname, given_names[0]).trim())]
+            };
+
+            parts.extend(given_names[1..].iter().cloned());
+            parts.join(", ")
+        }).collect::<Vec<String>>().join("; ")
+    }
+}
+
+// Returns the number of words, at the beginning of a list, that
+// make up the surname: that's usually just one, plus any particles
+// found in front of it
+fn surname_length(words: &[&str]) -> usize {
+    let mut length = 0;
+
+    while length < words.len() - 1 && PARTICLES.contains(&words[length].to_lowercase().as_str()) {
+        length += 1;
+    }
+
+    length + 1
+}
+
+fn normalize_spaces(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(people: &[Person]) -> Vec<(&str, &str)> {
+        people.iter().map(|person| (person.surname.as_str(), person.given_names.as_str())).collect()
+    }
+
+    fn round_trip(text: &str) -> String {
+        Person::format_list(&Person::parse_list(text))
+    }
+
+    #[test]
+    fn parse_family() {
+        assert_eq!(
+            names(&Person::parse_list("Rossi Mario, Anna")),
+            vec![("Rossi", "Mario"), ("Rossi", "Anna")],
+        );
+        assert_eq!(
+            names(&Person::parse_list("  Rossi   Mario Luigi ,Anna  ")),
+            vec![("Rossi", "Mario Luigi"), ("Rossi", "Anna")],
+        );
+    }
+
+    #[test]
+    fn parse_particles() {
+        assert_eq!(
+            names(&Person::parse_list("De' Medici Lorenzo")),
+            vec![("De' Medici", "Lorenzo")],
+        );
+        assert_eq!(
+            names(&Person::parse_list("Della Valle Diego, Andrea")),
+            vec![("Della Valle", "Diego"), ("Della Valle", "Andrea")],
+        );
+        // A particle alone is taken to be the surname
+        assert_eq!(names(&Person::parse_list("Di")), vec![("Di", "")]);
+    }
+
+    #[test]
+    fn parse_families() {
+        assert_eq!(
+            names(&Person::parse_list("Rossi Mario, Anna; Bianchi Luca")),
+            vec![("Rossi", "Mario"), ("Rossi", "Anna"), ("Bianchi", "Luca")],
+        );
+    }
+
+    #[test]
+    fn parse_empty_parts() {
+        assert!(Person::parse_list("").is_empty());
+        assert!(Person::parse_list(" ; , ").is_empty());
+        assert_eq!(
+            names(&Person::parse_list("Rossi Mario, , Anna;; Bianchi Luca;")),
+            vec![("Rossi", "Mario"), ("Rossi", "Anna"), ("Bianchi", "Luca")],
+        );
+    }
+
+    #[test]
+    fn format() {
+        let people = [
+            Person::new("Rossi", "Mario"),
+            Person::new("Rossi", "Anna"),
+            Person::new("", ""),
+            Person::new("Bianchi", "Luca"),
+        ];
+
+        assert_eq!(Person::format_list(&people), "Rossi Mario, Anna; Bianchi Luca");
+        assert_eq!(Person::format_list(&[]), "");
+    }
+
+    #[test]
+    fn formatted_round_trip() {
+        for text in &[
+            "Rossi Mario",
+            "Rossi Mario, Anna",
+            "De' Medici Lorenzo, Giuliano; Della Valle Diego",
+            "Rossi Mario; Bianchi Luca, Anna",
+            "Rossi",
+        ] {
+            assert_eq!(round_trip(text), *text);
+        }
+
+        // Spacing is normalized, and empty parts are dropped
+        assert_eq!(round_trip(" Rossi  Mario ,Anna ;; "), "Rossi Mario, Anna");
+    }
+
+    #[test]
+    fn empty_surname() {
+        let people = [Person::new("", "Mario"), Person::new("", "Anna")];
+        let text = Person::format_list(&people);
+
+        assert_eq!(text, ", Mario, Anna");
+        assert_eq!(names(&Person::parse_list(&text)), vec![("", "Mario"), ("", "Anna")]);
+    }
+}
@@ -0,0 +1,120 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::collections::HashMap;
+
+use crate::column;
+use crate::column::Column;
+use crate::validator::Validators;
+
+// Only fixes that can't possibly make things worse are offered
+pub enum Fix {
+    // Replace the value with the one provided
+    Set(String),
+    // Remove the record altogether
+    Remove,
+}
+
+// Something wrong with a value, which is identified by the position
+// of the record in the registry and the column
+pub struct Problem {
+    pub record: usize,
+    pub column: Column,
+    pub message: String,
+    pub fix: Option<Fix>,
+}
+
+impl Problem {
+    fn new(record: usize, column: Column, message: &str, fix: Option<Fix>) -> Self {
+        Self {
+            record,
+            column,
+            message: String::from(message),
+            fix,
+        }
+    }
+}
+
+// Dates are often typed using the wrong separator
+fn fix_date(date: &str) -> Option<String> {
+    let fixed: String = date.chars().map(|c| {
+        if c == '-' || c == '.' || c == ' ' { '/' } else { c }
+    }).collect();
+
+    column::parse_date(&fixed).map(|_| fixed)
+}
+
+pub fn scan(records: &[[String; Column::SIZE]], validators: &Validators) -> Vec<Problem> {
+    let mut ret = Vec::new();
+
+    let mut uses: HashMap<&str, usize> = HashMap::new();
+    for record in records {
+        let number = record[usize::from(Column::Number)].trim();
+
+        if !number.is_empty() {
+            *uses.entry(number).or_insert(0) += 1;
+        }
+    }
+
+    // Records without a number get the next ones in the sequence,
+    // same as inserted records
+    let mut next = column::next_number(records.iter().map(|record| {
+        record[usize::from(Column::Number)].trim()
+    }));
+
+    for (i, record) in records.iter().enumerate() {
+        for column in (0..Column::SIZE).map(Column::from) {
+            let value = &record[usize::from(column.clone())];
+
+            if value.trim() != value {
+                let fix = Fix::Set(String::from(value.trim()));
+                ret.push(Problem::new(i, column, "Leading or trailing spaces", Some(fix)));
+            }
+        }
+
+        // Usually left behind by an abandoned insert, in which case
+        // there's nothing worth keeping. Names might also have been
+        // cleared by accident, so the record is only offered for
+        // removal if there's nothing else in it
+        if record[usize::from(Column::People)].trim().is_empty() {
+            let blank = (0..Column::SIZE).map(Column::from).filter(|column| {
+                !matches!(column, Column::Number | Column::Date)
+            }).all(|column| record[usize::from(column)].trim().is_empty());
+
+            let fix = if blank { Some(Fix::Remove) } else { None };
+            ret.push(Problem::new(i, Column::People, "Missing", fix));
+        }
+
+        let number = record[usize::from(Column::Number)].trim();
+        if number.is_empty() {
+            ret.push(Problem::new(i, Column::Number, "Missing", Some(Fix::Set(next.to_string()))));
+            next += 1;
+        } else if number.parse::<i32>().is_err() {
+            ret.push(Problem::new(i, Column::Number, "Not a number", None));
+        } else if uses[number] > 1 {
+            let message = format!("Used by {} records", uses[number]);
+            ret.push(Problem::new(i, Column::Number, &message, None));
+        }
+
+        let date = record[usize::from(Column::Date)].trim();
+        if date.is_empty() {
+            ret.push(Problem::new(i, Column::Date, "Missing", None));
+        } else if column::parse_date(date).is_none() {
+            let fix = fix_date(date).map(Fix::Set);
+            ret.push(Problem::new(i, Column::Date, "Not a valid date", fix));
+        }
+
+        if record[usize::from(Column::ID)].trim().is_empty() {
+            ret.push(Problem::new(i, Column::ID, "Missing", None));
+        }
+
+        for column in (0..Column::SIZE).map(Column::from) {
+            if let Some(message) = validators.validate(&column, record) {
+                ret.push(Problem::new(i, column, &message, None));
+            }
+        }
+    }
+
+    ret
+}
@@ -0,0 +1,151 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::cardtemplate;
+use crate::cardtemplate::CardTemplate;
+use crate::ledger;
+use crate::ledger::Payment;
+use crate::settings::Settings;
+
+// Receipt numbers are progressive within each year, and written in
+// the form "number/year", eg. "12/2021"
+pub fn parse_number(receipt: &str) -> Option<(u32, i32)> {
+    let (number, year) = receipt.split_at(receipt.find('/')?);
+
+    Some((number.trim().parse().ok()?, year[1..].trim().parse().ok()?))
+}
+
+pub fn next_number(payments: &[Payment], year: i32) -> String {
+    let last = payments.iter().filter_map(|p| parse_number(&p.receipt)).filter(|(_, y)| {
+        *y == year
+    }).map(|(n, _)| n).max().unwrap_or(0);
+
+    format!("{}/{}", last + 1, year)
+}
+
+// Receipts are stored next to the document, in a directory named
+// after it, so that they can be printed again later
+pub fn path_for(document: &Path, receipt: &str) -> PathBuf {
+    let mut dir = String::new();
+
+    if let Some(stem) = document.file_stem().and_then(|s| s.to_str()) {
+        dir.push_str(stem);
+    }
+    dir.push_str(".receipts");
+
+    let mut ret = document.with_file_name(dir);
+    ret.push(format!("{}.pdf", receipt.replace('/', "-")));
+    ret
+}
+
+pub struct Receipt {
+    template: CardTemplate,
+    values: Vec<String>,
+    extra: Vec<(String, String)>,
+}
+
+impl Receipt {
+    pub fn new(settings: &Settings, record: &[String], payment: &Payment) -> Self {
+        let extra = vec![
+            (String::from("receipt"), payment.receipt.clone()),
+            (String::from("payment-date"), payment.date.clone()),
+            (String::from("amount"), ledger::format_amount(payment.amount)),
+            (String::from("method"), payment.method.clone()),
+            (String::from("operator"), payment.operator.clone()),
+            (String::from("association"), settings.association_name()),
+            (String::from("association-address"), settings.association_address()),
+            (String::from("association-fiscal-code"), settings.association_fiscal_code()),
+        ];
+
+        Self {
+            template: CardTemplate::load_receipt(),
+            values: record.to_vec(),
+            extra,
+        }
+    }
+
+    // The receipt takes up a whole page, the size of the template
+    pub fn export_pdf(&self, path: &Path) -> Result<(), cairo::Error> {
+        let width = cardtemplate::mm(self.template.width);
+        let height = cardtemplate::mm(self.template.height);
+
+        let surface = cairo::PdfSurface::new(width, height, path)?;
+        let cr = cairo::Context::new(&surface)?;
+
+        self.template.draw_with(&cr, &self.values, &self.extra);
+        cr.show_page()?;
+
+        surface.finish();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payment(receipt: &str) -> Payment {
+        Payment {
+            number: String::from("1"),
+            id: String::new(),
+            date: String::from("10/01/21"),
+            amount: 1000,
+            method: String::from("Cash"),
+            receipt: String::from(receipt),
+            operator: String::new(),
+        }
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_number("12/2021"), Some((12, 2021)));
+        assert_eq!(parse_number(" 3 / 2020 "), Some((3, 2020)));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_eq!(parse_number(""), None);
+        assert_eq!(parse_number("12"), None);
+        assert_eq!(parse_number("12-2021"), None);
+        assert_eq!(parse_number("A12/2021"), None);
+        assert_eq!(parse_number("12/"), None);
+        assert_eq!(parse_number("-1/2021"), None);
+    }
+
+    #[test]
+    fn progressive() {
+        let payments = vec![payment("1/2021"), payment(""), payment("3/2021"), payment("2/2021")];
+
+        assert_eq!(next_number(&payments, 2021), "4/2021");
+    }
+
+    #[test]
+    fn year_rollover() {
+        let payments = vec![payment("41/2020"), payment("42/2020"), payment("1/2021")];
+
+        assert_eq!(next_number(&payments, 2021), "2/2021");
+        assert_eq!(next_number(&payments, 2020), "43/2020");
+        assert_eq!(next_number(&payments, 2022), "1/2022");
+        assert_eq!(next_number(&[], 2021), "1/2021");
+    }
+
+    #[test]
+    fn unparsable() {
+        // Receipts written by hand don't count towards the sequence
+        let payments = vec![payment("2/2021"), payment("99"), payment("n. 50/2021"), payment("x/2021")];
+
+        assert_eq!(next_number(&payments, 2021), "3/2021");
+    }
+
+    #[test]
+    fn paths() {
+        let path = path_for(Path::new("/data/registry.csv"), "12/2021");
+
+        assert_eq!(path, Path::new("/data/registry.receipts/12-2021.pdf"));
+    }
+}
@@ -0,0 +1,167 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::path::Path;
+use std::rc::Rc;
+
+use ::gtk::prelude::*;
+
+use crate::cardtemplate;
+use crate::column::Column;
+
+// Sizes in millimeters
+const A4_WIDTH: f64 = 210.0;
+const A4_HEIGHT: f64 = 297.0;
+const MARGIN: f64 = 15.0;
+const HEADER_HEIGHT: f64 = 18.0;
+const FOOTER_HEIGHT: f64 = 8.0;
+const ROW_HEIGHT: f64 = 6.0;
+const PADDING: f64 = 1.5;
+
+// Columns included in the report, along with the fraction of the
+// available width each of them takes. Flags are internal and not
+// worth printing
+const COLUMNS: [(Column, f64); 5] = [
+    (Column::Number, 0.10),
+    (Column::People, 0.42),
+    (Column::Signature, 0.12),
+    (Column::ID, 0.22),
+    (Column::Date, 0.14),
+];
+
+// A paper version of the registry, with a header describing it
+// followed by one row per record. Column headers are repeated on
+// every page so that each page can be read on its own
+#[derive(Clone)]
+pub struct Report {
+    title: Rc<String>,
+    date: Rc<String>,
+    records: Rc<Vec<[String; Column::SIZE]>>,
+}
+
+impl Report {
+    pub fn new(title: &str, records: Vec<[String; Column::SIZE]>) -> Self {
+        Self {
+            title: Rc::new(String::from(title)),
+            date: Rc::new(chrono::Local::today().format("%d/%m/%Y").to_string()),
+            records: Rc::new(records),
+        }
+    }
+
+    fn rows_per_page() -> usize {
+        let available = A4_HEIGHT - 2.0 * MARGIN - HEADER_HEIGHT - FOOTER_HEIGHT;
+
+        // One row is taken by the column headers
+        (available / ROW_HEIGHT).floor() as usize - 1
+    }
+
+    pub fn n_pages(&self) -> i32 {
+        let per_page = Report::rows_per_page();
+
+        // Even an empty report gets a page, so that the header with
+        // the count is still produced
+        ((self.records.len() + per_page - 1) / per_page).max(1) as i32
+    }
+
+    pub fn draw_page(&self, cr: &cairo::Context, page: i32) {
+        let mm = cardtemplate::mm;
+        let per_page = Report::rows_per_page();
+        let width = A4_WIDTH - 2.0 * MARGIN;
+
+        cr.set_source_rgb(0.0, 0.0, 0.0);
+
+        // Header
+        self.draw_text(cr, "Sans Bold 14", &self.title, MARGIN, MARGIN, width);
+        let summary = format!("{} · {} members", self.date, self.records.len());
+        self.draw_text(cr, "Sans 9", &summary, MARGIN, MARGIN + 8.0, width);
+
+        // Column headers
+        let mut y = MARGIN + HEADER_HEIGHT;
+        let mut x = MARGIN;
+        for (column, fraction) in COLUMNS.iter() {
+            self.draw_text(cr, "Sans Bold 9", column.title(), x, y + PADDING, width * fraction);
+            x += width * fraction;
+        }
+        y += ROW_HEIGHT;
+
+        cr.set_line_width(0.5);
+        cr.move_to(mm(MARGIN), mm(y));
+        cr.line_to(mm(MARGIN + width), mm(y));
+        let _ = cr.stroke();
+
+        // Rows
+        let records = self.records.iter().skip(page as usize * per_page).take(per_page);
+
+        for record in records {
+            let mut x = MARGIN;
+            for (column, fraction) in COLUMNS.iter() {
+                let value = &record[usize::from(column.clone())];
+                self.draw_text(cr, "Sans 9", value, x, y + PADDING, width * fraction);
+                x += width * fraction;
+            }
+            y += ROW_HEIGHT;
+
+            cr.set_line_width(0.1);
+            cr.move_to(mm(MARGIN), mm(y));
+            cr.line_to(mm(MARGIN + width), mm(y));
+            let _ = cr.stroke();
+        }
+
+        // Footer
+        let footer = format!("Page {} of {}", page + 1, self.n_pages());
+        self.draw_text(cr, "Sans 8", &footer, MARGIN, A4_HEIGHT - MARGIN - FOOTER_HEIGHT / 2.0, width);
+    }
+
+    // Draws a single line of text, which is ellipsized if it doesn't
+    // fit in the given width. Positions are in millimeters
+    fn draw_text(&self, cr: &cairo::Context, font: &str, text: &str, x: f64, y: f64, width: f64) {
+        let mm = cardtemplate::mm;
+
+        if let Some(layout) = cardtemplate::create_layout(cr, font) {
+            layout.set_width((mm(width - PADDING) * pango::SCALE as f64) as i32);
+            layout.set_ellipsize(pango::EllipsizeMode::End);
+            layout.set_text(text);
+
+            cr.move_to(mm(x), mm(y));
+            pangocairo::show_layout(cr, &layout);
+        }
+    }
+
+    pub fn print<W: IsA<gtk::Window>>(&self, window: &W) -> Result<(), glib::Error> {
+        let page_setup = gtk::PageSetup::new();
+        page_setup.set_paper_size(&gtk::PaperSize::new(Some("iso_a4")));
+
+        let operation = gtk::PrintOperation::new();
+        operation.set_job_name(&self.title);
+        operation.set_default_page_setup(Some(&page_setup));
+        operation.set_use_full_page(true);
+        operation.set_unit(gtk::Unit::Points);
+        operation.set_n_pages(self.n_pages());
+
+        let _self = self.clone();
+        operation.connect_draw_page(move |_, context, page| {
+            if let Some(cr) = context.cairo_context() {
+                _self.draw_page(&cr, page);
+            }
+        });
+
+        operation.run(gtk::PrintOperationAction::PrintDialog, Some(window)).map(|_| ())
+    }
+
+    pub fn export_pdf(&self, path: &Path) -> Result<(), cairo::Error> {
+        let mm = cardtemplate::mm;
+
+        let surface = cairo::PdfSurface::new(mm(A4_WIDTH), mm(A4_HEIGHT), path)?;
+        let cr = cairo::Context::new(&surface)?;
+
+        for page in 0..self.n_pages() {
+            self.draw_page(&cr, page);
+            cr.show_page()?;
+        }
+
+        surface.finish();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::column::Column;
+use crate::ledger;
+
+#[derive(Clone)]
+pub struct Settings {
+    keyfile: glib::KeyFile,
+    path: PathBuf,
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        let mut path = glib::user_config_dir();
+        path.push("tesserama");
+        path.push("settings.ini");
+
+        let ret = Self {
+            keyfile: glib::KeyFile::new(),
+            path,
+        };
+        ret.load();
+        ret
+    }
+
+    fn load(&self) {
+        // A missing or invalid file simply means that we start
+        // from scratch, so errors can be safely ignored here
+        let flags = glib::KeyFileFlags::KEEP_COMMENTS;
+        let _ = self.keyfile.load_from_file(&self.path, flags);
+    }
+
+    fn save(&self) {
+        // Losing settings is annoying but not critical, so we don't
+        // want to bother the user if something goes wrong here
+        if let Some(parent) = self.path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let _ = self.keyfile.save_to_file(&self.path);
+    }
+
+    // Printed at the top of reports
+    pub fn association_name(&self) -> String {
+        self.keyfile.string("Association", "name")
+                    .map_or(String::from("Tesserama"), |name| name.to_string())
+    }
+
+    // Printed on receipts, along with the name
+    pub fn association_address(&self) -> String {
+        self.keyfile.string("Association", "address")
+                    .map_or(String::new(), |address| address.to_string())
+    }
+
+    pub fn association_fiscal_code(&self) -> String {
+        self.keyfile.string("Association", "fiscal-code")
+                    .map_or(String::new(), |code| code.to_string())
+    }
+
+    // Annual membership fee, in cents. Zero if not configured
+    pub fn membership_fee(&self) -> i64 {
+        self.keyfile.string("Association", "fee").ok()
+                    .and_then(|fee| ledger::parse_amount(&fee))
+                    .unwrap_or(0)
+    }
+
+    // Whether codes in the ID column should be checked against the
+    // names of the people in the record, on top of being valid
+    pub fn cross_check_codice_fiscale(&self) -> bool {
+        self.keyfile.boolean("Validation", "codice-fiscale-names").unwrap_or(false)
+    }
+
+    // Saved searches are stored one per group, in the order in
+    // which they should be displayed; an additional group keeps
+    // track of how many there are
+    fn saved_search_group(index: usize) -> String {
+        format!("Saved search {}", index)
+    }
+
+    pub fn saved_searches(&self) -> Vec<(String, String)> {
+        let mut ret = Vec::new();
+
+        let count = match self.keyfile.integer("Saved searches", "count") {
+            Ok(count) => count,
+            Err(_) => {
+                // Provide a few examples for first-time users
+                ret.push((String::from("New this year"), String::from("year:this")));
+                ret.push((String::from("Without ID"), String::from("id:")));
+                return ret;
+            },
+        };
+
+        for index in 0..count.max(0) as usize {
+            let group = Settings::saved_search_group(index);
+
+            let name = self.keyfile.string(&group, "name");
+            let query = self.keyfile.string(&group, "query");
+
+            if let (Ok(name), Ok(query)) = (name, query) {
+                ret.push((name.to_string(), query.to_string()));
+            }
+        }
+
+        ret
+    }
+
+    pub fn set_saved_searches(&self, searches: &[(String, String)]) {
+        let count = self.keyfile.integer("Saved searches", "count").unwrap_or(0);
+
+        // Drop all existing entries first, so that none are left
+        // behind when the list gets shorter
+        for index in 0..count.max(0) as usize {
+            let _ = self.keyfile.remove_group(&Settings::saved_search_group(index));
+        }
+
+        for (index, (name, query)) in searches.iter().enumerate() {
+            let group = Settings::saved_search_group(index);
+
+            self.keyfile.set_string(&group, "name", name);
+            self.keyfile.set_string(&group, "query", query);
+        }
+        self.keyfile.set_integer("Saved searches", "count", searches.len() as i32);
+
+        self.save();
+    }
+
+    // Per-document settings are stored in a separate group for
+    // each document, identified by its URI
+    fn document_group(uri: &str) -> String {
+        format!("Document {}", uri)
+    }
+
+    pub fn sort_order(&self, uri: &str) -> Option<(Column, gtk::SortType)> {
+        let group = Settings::document_group(uri);
+
+        let column = self.keyfile.integer(&group, "sort-column").ok()?;
+        let descending = self.keyfile.boolean(&group, "sort-descending").ok()?;
+
+        if column < 0 || column as usize >= Column::SIZE {
+            return None;
+        }
+
+        let order = if descending {
+            gtk::SortType::Descending
+        } else {
+            gtk::SortType::Ascending
+        };
+
+        Some((Column::from(column), order))
+    }
+
+    pub fn set_sort_order(&self, uri: &str, column: &Column, order: gtk::SortType) {
+        let group = Settings::document_group(uri);
+
+        self.keyfile.set_integer(&group, "sort-column", i32::from(column.clone()));
+        self.keyfile.set_boolean(&group, "sort-descending", order == gtk::SortType::Descending);
+
+        self.save();
+    }
+
+    // Called every time records are ranked again, so only touch the
+    // file if there's actually something to clear
+    pub fn clear_sort_order(&self, uri: &str) {
+        let group = Settings::document_group(uri);
+
+        if !self.keyfile.has_key(&group, "sort-column").unwrap_or(false) {
+            return;
+        }
+
+        let _ = self.keyfile.remove_key(&group, "sort-column");
+        let _ = self.keyfile.remove_key(&group, "sort-descending");
+
+        self.save();
+    }
+
+    // Groups of possible duplicates the user has said are not, as
+    // returned by Group::key()
+    pub fn dismissed_duplicates(&self, uri: &str) -> Vec<String> {
+        let group = Settings::document_group(uri);
+
+        self.keyfile.string_list(&group, "dismissed-duplicates").map_or(Vec::new(), |keys| {
+            keys.iter().map(|key| key.to_string()).collect()
+        })
+    }
+
+    pub fn set_dismissed_duplicates(&self, uri: &str, keys: &[String]) {
+        let group = Settings::document_group(uri);
+        let keys: Vec<&str> = keys.iter().map(|key| key.as_str()).collect();
+
+        self.keyfile.set_string_list(&group, "dismissed-duplicates", &keys);
+
+        self.save();
+    }
+}
@@ -0,0 +1,127 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+const HEADER: &str = "tesserama-signature 1";
+
+// Coordinates are stored as integers in this range, which is
+// plenty of precision for a signature and keeps files small
+const SCALE: f64 = 1000.0;
+
+// Signatures are captured in an area with this aspect ratio, and
+// should be drawn using the same one to avoid distortion
+pub const ASPECT_RATIO: f64 = 3.0;
+
+#[derive(Clone)]
+pub struct Signature {
+    // Each stroke is a list of points, with coordinates normalized
+    // to the [0, 1] range so that they can be drawn at any size
+    strokes: Vec<Vec<(f64, f64)>>,
+}
+
+impl Signature {
+    pub fn new() -> Self {
+        Self {
+            strokes: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strokes.is_empty()
+    }
+
+    pub fn start_stroke(&mut self, x: f64, y: f64) {
+        self.strokes.push(vec![(clamp(x), clamp(y))]);
+    }
+
+    pub fn extend_stroke(&mut self, x: f64, y: f64) {
+        if let Some(stroke) = self.strokes.last_mut() {
+            stroke.push((clamp(x), clamp(y)));
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Signature> {
+        let mut lines = text.lines();
+
+        if lines.next() != Some(HEADER) {
+            return None;
+        }
+
+        // Each line after the header contains a stroke, in the form
+        //
+        //   x1,y1 x2,y2 x3,y3 ...
+        let mut ret = Signature::new();
+
+        for line in lines {
+            let mut stroke = Vec::new();
+
+            for point in line.split_whitespace() {
+                let mut coords = point.split(',').map(|c| c.parse::<f64>());
+
+                if let (Some(Ok(x)), Some(Ok(y))) = (coords.next(), coords.next()) {
+                    stroke.push((clamp(x / SCALE), clamp(y / SCALE)));
+                }
+            }
+
+            if !stroke.is_empty() {
+                ret.strokes.push(stroke);
+            }
+        }
+
+        Some(ret)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut ret = String::from(HEADER);
+        ret.push('\n');
+
+        for stroke in &self.strokes {
+            let points: Vec<String> = stroke.iter().map(|(x, y)| {
+                format!("{},{}", (x * SCALE).round(), (y * SCALE).round())
+            }).collect();
+
+            ret.push_str(&points.join(" "));
+            ret.push('\n');
+        }
+
+        ret
+    }
+
+    pub fn draw(&self, cr: &cairo::Context, width: f64, height: f64) {
+        cr.set_source_rgb(0.0, 0.0, 0.0);
+        cr.set_line_width((height / 60.0).max(1.0));
+        cr.set_line_cap(cairo::LineCap::Round);
+        cr.set_line_join(cairo::LineJoin::Round);
+
+        for stroke in &self.strokes {
+            let (x, y) = stroke[0];
+            cr.move_to(x * width, y * height);
+
+            // A single point is drawn as a dot
+            if stroke.len() == 1 {
+                cr.line_to(x * width, y * height);
+            }
+            for (x, y) in &stroke[1..] {
+                cr.line_to(x * width, y * height);
+            }
+        }
+
+        let _ = cr.stroke();
+    }
+
+    pub fn thumbnail(&self, height: i32) -> Option<cairo::ImageSurface> {
+        let width = (height as f64 * ASPECT_RATIO) as i32;
+        let surface = cairo::ImageSurface::create(cairo::Format::ARgb32, width, height).ok()?;
+
+        {
+            let cr = cairo::Context::new(&surface).ok()?;
+            self.draw(&cr, width as f64, height as f64);
+        }
+
+        Some(surface)
+    }
+}
+
+fn clamp(value: f64) -> f64 {
+    value.clamp(0.0, 1.0)
+}
@@ -0,0 +1,147 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ::gtk::prelude::*;
+
+use crate::signature;
+use crate::signature::Signature;
+
+#[derive(Clone)]
+pub struct SignatureDialog {
+    parent: gtk::Dialog,
+    area: gtk::DrawingArea,
+    signature: Rc<RefCell<Signature>>,
+}
+
+impl SignatureDialog {
+    pub fn new<W: IsA<gtk::Window>>(window: &W) -> Self {
+        let ret = Self {
+            parent: gtk::Dialog::with_buttons(
+                Some("Capture signature"),
+                Some(window),
+                gtk::DialogFlags::MODAL,
+                &[("Cancel", gtk::ResponseType::Cancel), ("Save", gtk::ResponseType::Ok)],
+            ),
+            area: gtk::DrawingArea::new(),
+            signature: Rc::new(RefCell::new(Signature::new())),
+        };
+        ret.setup();
+        ret
+    }
+
+    fn setup(&self) {
+        self.parent.set_default_response(gtk::ResponseType::Ok);
+
+        let height = 200;
+        self.area.set_size_request((height as f64 * signature::ASPECT_RATIO) as i32, height);
+        self.area.add_events(
+            gdk::EventMask::BUTTON_PRESS_MASK |
+            gdk::EventMask::BUTTON_MOTION_MASK |
+            gdk::EventMask::BUTTON_RELEASE_MASK
+        );
+
+        let _self = self.clone();
+        self.area.connect_draw(move |area, cr| {
+            _self.draw(area, cr)
+        });
+
+        let _self = self.clone();
+        self.area.connect_button_press_event(move |area, event| {
+            _self.button_pressed(area, event)
+        });
+
+        let _self = self.clone();
+        self.area.connect_motion_notify_event(move |area, event| {
+            _self.motion_notified(area, event)
+        });
+
+        let frame = gtk::Frame::new(None);
+        frame.add(&self.area);
+
+        let hint = gtk::Label::new(Some("Sign in the area above using the mouse or a pen tablet."));
+        hint.style_context().add_class("dim-label");
+
+        let clearbutton = gtk::Button::with_label("Clear");
+        clearbutton.set_halign(gtk::Align::Start);
+        let _self = self.clone();
+        clearbutton.connect_clicked(move |_| {
+            _self.clear_clicked();
+        });
+
+        let contents = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        contents.set_border_width(12);
+        contents.pack_start(&frame, true, true, 0);
+        contents.pack_start(&hint, false, false, 0);
+        contents.pack_start(&clearbutton, false, false, 0);
+        self.parent.content_area().add(&contents);
+    }
+
+    // Returns the captured signature, or None if the user has
+    // cancelled the operation. An empty signature means that the
+    // existing one, if any, should be removed
+    pub fn run(&self) -> Option<Signature> {
+        self.parent.show_all();
+
+        let ret = match self.parent.run() {
+            gtk::ResponseType::Ok => Some(self.signature.borrow().clone()),
+            _ => None,
+        };
+
+        unsafe {
+            self.parent.destroy();
+        }
+
+        ret
+    }
+
+    // Signal handlers
+
+    fn draw(&self, area: &gtk::DrawingArea, cr: &cairo::Context) -> glib::signal::Inhibit {
+        let width = area.allocated_width() as f64;
+        let height = area.allocated_height() as f64;
+
+        cr.set_source_rgb(1.0, 1.0, 1.0);
+        let _ = cr.paint();
+
+        self.signature.borrow().draw(cr, width, height);
+
+        glib::signal::Inhibit(false)
+    }
+
+    fn button_pressed(&self, area: &gtk::DrawingArea, event: &gdk::EventButton) -> glib::signal::Inhibit {
+        let (x, y) = event.position();
+
+        if event.button() == 1 {
+            let width = area.allocated_width() as f64;
+            let height = area.allocated_height() as f64;
+
+            self.signature.borrow_mut().start_stroke(x / width, y / height);
+            area.queue_draw();
+        }
+
+        glib::signal::Inhibit(true)
+    }
+
+    fn motion_notified(&self, area: &gtk::DrawingArea, event: &gdk::EventMotion) -> glib::signal::Inhibit {
+        let (x, y) = event.position();
+
+        if event.state().contains(gdk::ModifierType::BUTTON1_MASK) {
+            let width = area.allocated_width() as f64;
+            let height = area.allocated_height() as f64;
+
+            self.signature.borrow_mut().extend_stroke(x / width, y / height);
+            area.queue_draw();
+        }
+
+        glib::signal::Inhibit(true)
+    }
+
+    fn clear_clicked(&self) {
+        *self.signature.borrow_mut() = Signature::new();
+        self.area.queue_draw();
+    }
+}
@@ -0,0 +1,381 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use rusqlite::types::Value;
+use rusqlite::OptionalExtension;
+
+use crate::column::Column;
+use crate::history::Entry;
+use crate::ledger::Payment;
+use crate::liststore::ListStore;
+use crate::signature::Signature;
+use crate::storage;
+use crate::storage::Storage;
+
+// Bumped whenever the schema changes, so that older databases can
+// be upgraded when they're opened
+const SCHEMA_VERSION: i32 = 2;
+
+const PAYMENT_COLUMNS: [&str; 7] = ["number", "id", "date", "amount", "method", "receipt", "operator"];
+
+// Records are stored in a table with one column for each of ours and
+// an additional one keeping track of the order records are displayed
+// in. Payments, signatures, attendance lists and history each get a
+// table of their own
+pub struct SqliteStorage {
+    path: PathBuf,
+}
+
+impl SqliteStorage {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+        }
+    }
+
+    fn columns() -> Vec<&'static str> {
+        (0..Column::SIZE).map(|i| Column::from(i).name()).collect()
+    }
+
+    // Any SQLite database is handed to us, so make sure it's one of
+    // ours, or a brand new one, before touching it
+    fn connect(&self) -> Result<rusqlite::Connection, storage::Error> {
+        let connection = rusqlite::Connection::open(&self.path)?;
+
+        let version: i32 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        if version > SCHEMA_VERSION {
+            return Err(storage::Error::Invalid(String::from(
+                "The database has been created by a newer version of the application",
+            )));
+        }
+
+        let tables: i64 = connection.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get(0))?;
+        let members: i64 = connection.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'members'",
+            [],
+            |row| row.get(0),
+        )?;
+
+        if tables > 0 && members == 0 {
+            return Err(storage::Error::Invalid(String::from(
+                "The database doesn't contain a registry",
+            )));
+        }
+
+        if version < 1 {
+            let columns: Vec<String> = SqliteStorage::columns().iter().map(|name| {
+                format!("\"{}\" TEXT NOT NULL DEFAULT ''", name)
+            }).collect();
+
+            connection.execute_batch(&format!(
+                "BEGIN;
+                 CREATE TABLE IF NOT EXISTS members (position INTEGER PRIMARY KEY, {});
+                 CREATE INDEX IF NOT EXISTS members_number ON members (\"number\");
+                 PRAGMA user_version = 1;
+                 COMMIT;",
+                columns.join(", "),
+            ))?;
+        }
+
+        if version < 2 {
+            connection.execute_batch(&format!(
+                "BEGIN;
+                 CREATE TABLE IF NOT EXISTS payments (
+                     position INTEGER PRIMARY KEY,
+                     \"number\" TEXT NOT NULL DEFAULT '',
+                     \"id\" TEXT NOT NULL DEFAULT '',
+                     \"date\" TEXT NOT NULL DEFAULT '',
+                     \"amount\" INTEGER NOT NULL DEFAULT 0,
+                     \"method\" TEXT NOT NULL DEFAULT '',
+                     \"receipt\" TEXT NOT NULL DEFAULT '',
+                     \"operator\" TEXT NOT NULL DEFAULT ''
+                 );
+                 CREATE TABLE IF NOT EXISTS signatures (
+                     \"number\" TEXT PRIMARY KEY,
+                     strokes TEXT NOT NULL
+                 );
+                 CREATE TABLE IF NOT EXISTS attendance (
+                     id INTEGER PRIMARY KEY,
+                     event TEXT NOT NULL,
+                     \"number\" TEXT NOT NULL,
+                     \"time\" TEXT NOT NULL DEFAULT ''
+                 );
+                 CREATE INDEX IF NOT EXISTS attendance_event ON attendance (event);
+                 CREATE TABLE IF NOT EXISTS history (
+                     id INTEGER PRIMARY KEY,
+                     \"time\" TEXT NOT NULL,
+                     message TEXT NOT NULL
+                 );
+                 PRAGMA user_version = {};
+                 COMMIT;",
+                SCHEMA_VERSION,
+            ))?;
+        }
+
+        Ok(connection)
+    }
+
+    // Brings a table whose rows are identified by their position in
+    // line with the rows provided. Only rows that have actually
+    // changed are written, which for the typical save, where a few
+    // records have been edited or appended, is a tiny fraction of
+    // the table. Removing a record shifts all the ones after it, so
+    // they have to be written again, but that's fairly uncommon.
+    //
+    // Everything happens in a single transaction, so the database is
+    // never left in an inconsistent state
+    fn sync(&self, table: &str, columns: &[&str], rows: &[Vec<Value>]) -> Result<(), storage::Error> {
+        let mut connection = self.connect()?;
+        let transaction = connection.transaction()?;
+
+        let quoted: Vec<String> = columns.iter().map(|name| format!("\"{}\"", name)).collect();
+
+        let mut existing: HashMap<i64, Vec<Value>> = HashMap::new();
+        {
+            let mut statement = transaction.prepare(&format!(
+                "SELECT position, {} FROM {}",
+                quoted.join(", "),
+                table,
+            ))?;
+
+            let found = statement.query_map([], |row| {
+                let values = (1..=columns.len()).map(|i| row.get(i)).collect::<rusqlite::Result<Vec<Value>>>()?;
+
+                Ok((row.get::<_, i64>(0)?, values))
+            })?;
+
+            for row in found {
+                let (position, values) = row?;
+                existing.insert(position, values);
+            }
+        }
+
+        {
+            let assignments: Vec<String> = quoted.iter().enumerate().map(|(i, name)| {
+                format!("{} = ?{}", name, i + 2)
+            }).collect();
+            let placeholders: Vec<String> = (1..=columns.len() + 1).map(|i| format!("?{}", i)).collect();
+
+            let mut update = transaction.prepare(&format!(
+                "UPDATE {} SET {} WHERE position = ?1",
+                table,
+                assignments.join(", "),
+            ))?;
+            let mut insert = transaction.prepare(&format!(
+                "INSERT INTO {} (position, {}) VALUES ({})",
+                table,
+                quoted.join(", "),
+                placeholders.join(", "),
+            ))?;
+
+            for (position, values) in rows.iter().enumerate() {
+                let position = position as i64;
+
+                let mut params: Vec<&dyn rusqlite::ToSql> = vec![&position];
+                params.extend(values.iter().map(|value| value as &dyn rusqlite::ToSql));
+
+                match existing.get(&position) {
+                    Some(current) if current == values => {},
+                    Some(_) => { update.execute(params.as_slice())?; },
+                    None => { insert.execute(params.as_slice())?; },
+                }
+            }
+        }
+
+        transaction.execute(
+            &format!("DELETE FROM {} WHERE position < 0 OR position >= ?1", table),
+            [rows.len() as i64],
+        )?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load(&self) -> Result<Vec<[String; Column::SIZE]>, storage::Error> {
+        let connection = self.connect()?;
+
+        let columns: Vec<String> = SqliteStorage::columns().iter().map(|name| {
+            format!("\"{}\"", name)
+        }).collect();
+        let mut statement = connection.prepare(&format!(
+            "SELECT {} FROM members ORDER BY position",
+            columns.join(", "),
+        ))?;
+
+        let rows = statement.query_map([], |row| {
+            let mut values = ListStore::new_row();
+
+            for (i, value) in values.iter_mut().enumerate() {
+                *value = row.get(i)?;
+            }
+
+            Ok(values)
+        })?;
+
+        let mut ret = Vec::new();
+        for row in rows {
+            ret.push(row?);
+        }
+
+        Ok(ret)
+    }
+
+    fn save(&self, records: &[[String; Column::SIZE]]) -> Result<(), storage::Error> {
+        let rows: Vec<Vec<Value>> = records.iter().map(|record| {
+            record.iter().map(|value| Value::Text(value.clone())).collect()
+        }).collect();
+
+        self.sync("members", &SqliteStorage::columns(), &rows)
+    }
+
+    fn load_payments(&self) -> Result<Vec<Payment>, storage::Error> {
+        let connection = self.connect()?;
+
+        let columns: Vec<String> = PAYMENT_COLUMNS.iter().map(|name| format!("\"{}\"", name)).collect();
+        let mut statement = connection.prepare(&format!(
+            "SELECT {} FROM payments ORDER BY position",
+            columns.join(", "),
+        ))?;
+
+        let rows = statement.query_map([], |row| {
+            Ok(Payment {
+                number: row.get(0)?,
+                id: row.get(1)?,
+                date: row.get(2)?,
+                amount: row.get(3)?,
+                method: row.get(4)?,
+                receipt: row.get(5)?,
+                operator: row.get(6)?,
+            })
+        })?;
+
+        let mut ret = Vec::new();
+        for row in rows {
+            ret.push(row?);
+        }
+
+        Ok(ret)
+    }
+
+    fn save_payments(&self, payments: &[Payment]) -> Result<(), storage::Error> {
+        let rows: Vec<Vec<Value>> = payments.iter().map(|payment| {
+            vec![
+                Value::Text(payment.number.clone()),
+                Value::Text(payment.id.clone()),
+                Value::Text(payment.date.clone()),
+                Value::Integer(payment.amount),
+                Value::Text(payment.method.clone()),
+                Value::Text(payment.receipt.clone()),
+                Value::Text(payment.operator.clone()),
+            ]
+        }).collect();
+
+        self.sync("payments", &PAYMENT_COLUMNS, &rows)
+    }
+
+    fn load_signature(&self, number: &str) -> Result<Option<Signature>, storage::Error> {
+        let connection = self.connect()?;
+
+        let text: Option<String> = connection.query_row(
+            "SELECT strokes FROM signatures WHERE \"number\" = ?1",
+            [number],
+            |row| row.get(0),
+        ).optional()?;
+
+        Ok(text.and_then(|text| Signature::parse(&text)))
+    }
+
+    fn save_signature(&self, number: &str, signature: &Signature) -> Result<(), storage::Error> {
+        let connection = self.connect()?;
+
+        if signature.is_empty() {
+            connection.execute("DELETE FROM signatures WHERE \"number\" = ?1", [number])?;
+        } else {
+            connection.execute(
+                "INSERT OR REPLACE INTO signatures (\"number\", strokes) VALUES (?1, ?2)",
+                rusqlite::params![number, signature.to_text()],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn events(&self) -> Result<Vec<String>, storage::Error> {
+        let connection = self.connect()?;
+
+        let mut statement = connection.prepare("SELECT DISTINCT event FROM attendance ORDER BY event")?;
+        let rows = statement.query_map([], |row| row.get(0))?;
+
+        let mut ret = Vec::new();
+        for row in rows {
+            ret.push(row?);
+        }
+
+        Ok(ret)
+    }
+
+    fn load_attendance(&self, event: &str) -> Result<Vec<(String, String)>, storage::Error> {
+        let connection = self.connect()?;
+
+        let mut statement = connection.prepare(
+            "SELECT \"number\", \"time\" FROM attendance WHERE event = ?1 ORDER BY id",
+        )?;
+        let rows = statement.query_map([event], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        let mut ret = Vec::new();
+        for row in rows {
+            ret.push(row?);
+        }
+
+        Ok(ret)
+    }
+
+    fn add_attendance(&self, event: &str, number: &str, time: &str) -> Result<(), storage::Error> {
+        let connection = self.connect()?;
+
+        connection.execute(
+            "INSERT INTO attendance (event, \"number\", \"time\") VALUES (?1, ?2, ?3)",
+            [event, number, time],
+        )?;
+
+        Ok(())
+    }
+
+    fn load_history(&self) -> Result<Vec<Entry>, storage::Error> {
+        let connection = self.connect()?;
+
+        let mut statement = connection.prepare("SELECT \"time\", message FROM history ORDER BY id")?;
+        let rows = statement.query_map([], |row| {
+            Ok(Entry {
+                time: row.get(0)?,
+                message: row.get(1)?,
+            })
+        })?;
+
+        let mut ret = Vec::new();
+        for row in rows {
+            ret.push(row?);
+        }
+
+        Ok(ret)
+    }
+
+    fn append_history(&self, entry: &Entry) -> Result<(), storage::Error> {
+        let connection = self.connect()?;
+
+        connection.execute(
+            "INSERT INTO history (\"time\", message) VALUES (?1, ?2)",
+            [&entry.time, &entry.message],
+        )?;
+
+        Ok(())
+    }
+}
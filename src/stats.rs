@@ -0,0 +1,101 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::collections::BTreeMap;
+use std::collections::HashSet;
+
+use chrono::Datelike;
+
+use crate::column;
+use crate::column::Column;
+use crate::needle;
+
+// Card numbers are grouped in blocks of this size when looking at
+// how the available range is being used
+pub const RANGE_SIZE: i32 = 100;
+
+// Figures about the registry, as presented to the board
+pub struct Stats {
+    pub total: usize,
+    // Cards issued in each year, in chronological order
+    pub per_year: Vec<(i32, usize)>,
+    // The most recent year cards have been issued in, and the cards
+    // issued in each of its months
+    pub year: Option<i32>,
+    pub per_month: [usize; 12],
+    // Compared to the previous year: members who have renewed their
+    // card, members who haven't and members who have joined
+    pub renewals: usize,
+    pub lapses: usize,
+    pub new: usize,
+    // Card numbers in use, and how they're spread across blocks
+    pub lowest: Option<i32>,
+    pub highest: Option<i32>,
+    pub numbers: usize,
+    pub duplicates: usize,
+    pub per_range: Vec<(i32, usize)>,
+}
+
+impl Stats {
+    pub fn new(records: &[[String; Column::SIZE]]) -> Self {
+        let mut per_year = BTreeMap::new();
+        // Members are identified by their names, since a new card
+        // with a new number is issued every year
+        let mut members: BTreeMap<i32, HashSet<String>> = BTreeMap::new();
+
+        for record in records {
+            if let Some(date) = column::parse_date(&record[usize::from(Column::Date)]) {
+                *per_year.entry(date.year()).or_insert(0) += 1;
+
+                let people = needle::fold(&record[usize::from(Column::People)]);
+                if !people.trim().is_empty() {
+                    members.entry(date.year()).or_default().insert(people);
+                }
+            }
+        }
+
+        let year = per_year.keys().next_back().copied();
+
+        let mut per_month = [0; 12];
+        for record in records {
+            if let Some(date) = column::parse_date(&record[usize::from(Column::Date)]) {
+                if Some(date.year()) == year {
+                    per_month[date.month0() as usize] += 1;
+                }
+            }
+        }
+
+        let empty = HashSet::new();
+        let current = year.and_then(|year| members.get(&year)).unwrap_or(&empty);
+        let previous = year.and_then(|year| members.get(&(year - 1))).unwrap_or(&empty);
+
+        let mut numbers: Vec<i32> = records.iter().filter_map(|record| {
+            record[usize::from(Column::Number)].parse::<i32>().ok()
+        }).collect();
+        numbers.sort_unstable();
+
+        let mut per_range: BTreeMap<i32, usize> = BTreeMap::new();
+        for number in &numbers {
+            *per_range.entry(number.div_euclid(RANGE_SIZE) * RANGE_SIZE).or_insert(0) += 1;
+        }
+
+        let count = numbers.len();
+        numbers.dedup();
+
+        Self {
+            total: records.len(),
+            per_year: per_year.into_iter().collect(),
+            year,
+            per_month,
+            renewals: current.intersection(previous).count(),
+            lapses: previous.difference(current).count(),
+            new: current.difference(previous).count(),
+            lowest: numbers.first().copied(),
+            highest: numbers.last().copied(),
+            numbers: numbers.len(),
+            duplicates: count - numbers.len(),
+            per_range: per_range.into_iter().collect(),
+        }
+    }
+}
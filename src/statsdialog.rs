@@ -0,0 +1,236 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use ::gtk::prelude::*;
+
+use crate::column::Column;
+use crate::stats;
+use crate::stats::Stats;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun",
+    "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+#[derive(Clone)]
+pub struct StatsDialog {
+    parent: gtk::Dialog,
+    matchingbutton: gtk::CheckButton,
+    figures: gtk::Label,
+    ranges: gtk::Label,
+    monthtitle: gtk::Label,
+    yearchart: gtk::DrawingArea,
+    monthchart: gtk::DrawingArea,
+    all: Rc<Vec<[String; Column::SIZE]>>,
+    matching: Rc<Option<Vec<[String; Column::SIZE]>>>,
+    stats: Rc<RefCell<Stats>>,
+}
+
+impl StatsDialog {
+    // Matching records are only provided while searching, in which
+    // case the user can choose which ones the figures refer to
+    pub fn new<W: IsA<gtk::Window>>(window: &W, all: Vec<[String; Column::SIZE]>, matching: Option<Vec<[String; Column::SIZE]>>) -> Self {
+        let ret = Self {
+            parent: gtk::Dialog::with_buttons(
+                Some("Statistics"),
+                Some(window),
+                gtk::DialogFlags::MODAL,
+                &[("Close", gtk::ResponseType::Close)],
+            ),
+            matchingbutton: gtk::CheckButton::with_label("Only members matching the current search"),
+            figures: gtk::Label::new(None),
+            ranges: gtk::Label::new(None),
+            monthtitle: gtk::Label::new(None),
+            yearchart: gtk::DrawingArea::new(),
+            monthchart: gtk::DrawingArea::new(),
+            stats: Rc::new(RefCell::new(Stats::new(&all))),
+            all: Rc::new(all),
+            matching: Rc::new(matching),
+        };
+        ret.setup();
+        ret
+    }
+
+    fn setup(&self) {
+        self.parent.set_default_size(700, -1);
+
+        self.matchingbutton.set_sensitive(self.matching.is_some());
+        let _self = self.clone();
+        self.matchingbutton.connect_toggled(move |_| {
+            _self.matching_toggled();
+        });
+
+        self.figures.set_xalign(0.0);
+        self.figures.set_yalign(0.0);
+        self.figures.set_selectable(true);
+
+        self.ranges.set_xalign(0.0);
+        self.ranges.set_yalign(0.0);
+        self.ranges.set_selectable(true);
+
+        let figuresbox = gtk::Box::new(gtk::Orientation::Horizontal, 24);
+        figuresbox.pack_start(&self.figures, true, true, 0);
+        figuresbox.pack_start(&self.ranges, true, true, 0);
+
+        self.yearchart.set_size_request(-1, 160);
+        let _self = self.clone();
+        self.yearchart.connect_draw(move |area, cr| {
+            _self.draw_year_chart(area, cr)
+        });
+
+        self.monthchart.set_size_request(-1, 160);
+        let _self = self.clone();
+        self.monthchart.connect_draw(move |area, cr| {
+            _self.draw_month_chart(area, cr)
+        });
+
+        let yeartitle = gtk::Label::new(None);
+        yeartitle.set_markup("<b>Cards issued per year</b>");
+        yeartitle.set_xalign(0.0);
+        self.monthtitle.set_xalign(0.0);
+
+        let contents = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        contents.set_border_width(12);
+        contents.pack_start(&self.matchingbutton, false, false, 0);
+        contents.pack_start(&figuresbox, false, false, 0);
+        contents.pack_start(&yeartitle, false, false, 0);
+        contents.pack_start(&self.yearchart, false, false, 0);
+        contents.pack_start(&self.monthtitle, false, false, 0);
+        contents.pack_start(&self.monthchart, false, false, 0);
+        self.parent.content_area().add(&contents);
+
+        self.update();
+    }
+
+    fn update(&self) {
+        let records: &[[String; Column::SIZE]] = match &*self.matching {
+            Some(matching) if self.matchingbutton.is_active() => matching,
+            _ => &self.all,
+        };
+        *self.stats.borrow_mut() = Stats::new(records);
+
+        let stats = self.stats.borrow();
+
+        let mut figures = format!("<b>Members</b>\n{} records\n", stats.total);
+
+        if let Some(year) = stats.year {
+            figures.push_str(&format!(
+                "\n<b>{} compared to {}</b>\n{} renewals\n{} lapses\n{} new members\n",
+                year, year - 1, stats.renewals, stats.lapses, stats.new,
+            ));
+        }
+        self.figures.set_markup(&figures);
+
+        let mut ranges = String::from("<b>Card numbers</b>\n");
+        match (stats.lowest, stats.highest) {
+            (Some(lowest), Some(highest)) => {
+                let span = (highest - lowest + 1) as usize;
+                ranges.push_str(&format!(
+                    "{} to {}, {} in use, {} unused\n",
+                    lowest, highest, stats.numbers, span - stats.numbers,
+                ));
+                if stats.duplicates > 0 {
+                    ranges.push_str(&format!("{} used more than once\n", stats.duplicates));
+                }
+                for (start, count) in &stats.per_range {
+                    ranges.push_str(&format!(
+                        "{}–{}: {}\n",
+                        start, start + stats::RANGE_SIZE - 1, count,
+                    ));
+                }
+            },
+            _ => ranges.push_str("None in use\n"),
+        }
+        self.ranges.set_markup(ranges.trim_end());
+
+        self.monthtitle.set_markup(&match stats.year {
+            Some(year) => format!("<b>Cards issued per month in {}</b>", year),
+            None => String::from("<b>Cards issued per month</b>"),
+        });
+
+        self.yearchart.queue_draw();
+        self.monthchart.queue_draw();
+    }
+
+    // Draws a simple bar chart filling the area, with the label and
+    // value for each bar displayed below and above it respectively
+    fn draw_bars(area: &gtk::DrawingArea, cr: &cairo::Context, bars: &[(String, usize)]) {
+        let width = area.allocated_width() as f64;
+        let height = area.allocated_height() as f64;
+        let text_height = 16.0;
+
+        let style = area.style_context();
+        let color = style.color(style.state());
+        cr.set_source_rgba(color.red, color.green, color.blue, color.alpha);
+
+        if bars.is_empty() {
+            return;
+        }
+
+        let max = bars.iter().map(|(_, value)| *value).max().unwrap_or(0).max(1) as f64;
+        let slot = width / bars.len() as f64;
+        let bar_width = (slot * 0.6).min(40.0);
+        let available = height - 2.0 * text_height;
+
+        for (i, (label, value)) in bars.iter().enumerate() {
+            let center = slot * (i as f64 + 0.5);
+            let bar_height = available * *value as f64 / max;
+            let top = text_height + available - bar_height;
+
+            cr.rectangle(center - bar_width / 2.0, top, bar_width, bar_height);
+            let _ = cr.fill();
+
+            StatsDialog::draw_label(cr, &value.to_string(), center, top - text_height);
+            StatsDialog::draw_label(cr, label, center, height - text_height);
+        }
+    }
+
+    fn draw_label(cr: &cairo::Context, text: &str, center: f64, top: f64) {
+        if let Some(layout) = pangocairo::create_layout(cr) {
+            layout.set_text(text);
+
+            let (width, _) = layout.pixel_size();
+            cr.move_to(center - width as f64 / 2.0, top);
+            pangocairo::show_layout(cr, &layout);
+        }
+    }
+
+    pub fn run(&self) {
+        self.parent.show_all();
+        self.parent.run();
+
+        unsafe {
+            self.parent.destroy();
+        }
+    }
+
+    // Signal handlers
+
+    fn matching_toggled(&self) {
+        self.update();
+    }
+
+    fn draw_year_chart(&self, area: &gtk::DrawingArea, cr: &cairo::Context) -> glib::signal::Inhibit {
+        let bars: Vec<(String, usize)> = self.stats.borrow().per_year.iter().map(|(year, count)| {
+            (year.to_string(), *count)
+        }).collect();
+
+        StatsDialog::draw_bars(area, cr, &bars);
+
+        glib::signal::Inhibit(false)
+    }
+
+    fn draw_month_chart(&self, area: &gtk::DrawingArea, cr: &cairo::Context) -> glib::signal::Inhibit {
+        let bars: Vec<(String, usize)> = MONTHS.iter().zip(self.stats.borrow().per_month.iter()).map(|(month, count)| {
+            (month.to_string(), *count)
+        }).collect();
+
+        StatsDialog::draw_bars(area, cr, &bars);
+
+        glib::signal::Inhibit(false)
+    }
+}
@@ -0,0 +1,137 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+use crate::column::Column;
+use crate::history::Entry;
+use crate::ledger::Payment;
+use crate::signature::Signature;
+use crate::csvstorage::CsvStorage;
+use crate::sqlitestorage::SqliteStorage;
+
+// Every SQLite database starts with this string
+const SQLITE_MAGIC: &[u8] = b"SQLite format 3\0";
+
+#[derive(Debug)]
+pub enum Error {
+    Csv(csv::Error),
+    Sqlite(rusqlite::Error),
+    Io(io::Error),
+    // The data is there, but not in a shape the application can
+    // make sense of
+    Invalid(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Csv(e) => e.fmt(f),
+            Error::Sqlite(e) => e.fmt(f),
+            Error::Io(e) => e.fmt(f),
+            Error::Invalid(message) => message.fmt(f),
+        }
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(e: csv::Error) -> Self {
+        Error::Csv(e)
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self {
+        Error::Sqlite(e)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+// The place records, and everything related to them, are loaded
+// from and saved to. The rest of the application only deals with
+// records, and doesn't care how they are actually stored.
+//
+// Records are only saved when the user asks for it, while all other
+// data is stored as soon as it changes
+pub trait Storage {
+    fn load(&self) -> Result<Vec<[String; Column::SIZE]>, Error>;
+    fn save(&self, records: &[[String; Column::SIZE]]) -> Result<(), Error>;
+
+    fn load_payments(&self) -> Result<Vec<Payment>, Error>;
+    fn save_payments(&self, payments: &[Payment]) -> Result<(), Error>;
+
+    // Signatures are looked up by card number. Saving an empty one
+    // removes it
+    fn load_signature(&self, number: &str) -> Result<Option<Signature>, Error>;
+    fn save_signature(&self, number: &str, signature: &Signature) -> Result<(), Error>;
+
+    // Attendance lists only ever grow, one member at a time: each
+    // entry is made of the card number and the time of arrival
+    fn events(&self) -> Result<Vec<String>, Error>;
+    fn load_attendance(&self, event: &str) -> Result<Vec<(String, String)>, Error>;
+    fn add_attendance(&self, event: &str, number: &str, time: &str) -> Result<(), Error>;
+
+    fn load_history(&self) -> Result<Vec<Entry>, Error>;
+    fn append_history(&self, entry: &Entry) -> Result<(), Error>;
+}
+
+// Existing files are recognized by their contents, while new ones
+// are recognized by their extension. Anything that's not an SQLite
+// database is assumed to be CSV, which is what older versions of
+// the application used exclusively
+pub fn open(path: &Path) -> Box<dyn Storage> {
+    if is_sqlite(path) {
+        Box::new(SqliteStorage::new(path))
+    } else {
+        Box::new(CsvStorage::new(path))
+    }
+}
+
+// Used when converting a document to a different format. Records are
+// passed in explicitly, so that unsaved changes are not lost, along
+// with the card numbers their signatures are currently stored under
+pub fn migrate(from: &dyn Storage, to: &dyn Storage, records: &[[String; Column::SIZE]], keys: &[String]) -> Result<(), Error> {
+    to.save(records)?;
+    to.save_payments(&from.load_payments()?)?;
+
+    for (record, key) in records.iter().zip(keys) {
+        if let Some(signature) = from.load_signature(key)? {
+            to.save_signature(&record[usize::from(Column::Number)], &signature)?;
+        }
+    }
+
+    for event in from.events()? {
+        for (number, time) in from.load_attendance(&event)? {
+            to.add_attendance(&event, &number, &time)?;
+        }
+    }
+
+    for entry in from.load_history()? {
+        to.append_history(&entry)?;
+    }
+
+    Ok(())
+}
+
+pub fn is_sqlite(path: &Path) -> bool {
+    let mut magic = [0; 16];
+
+    match File::open(path).and_then(|mut file| file.read_exact(&mut magic)) {
+        Ok(_) => magic == SQLITE_MAGIC,
+        Err(_) => {
+            let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+
+            matches!(extension.as_deref(), Some("sqlite") | Some("sqlite3") | Some("db"))
+        },
+    }
+}
@@ -0,0 +1,50 @@
+// Tesserama - Simple membership cards manager
+// Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use crate::codicefiscale::CodiceFiscale;
+use crate::column::Column;
+use crate::settings::Settings;
+
+// Checks the values found in a column, possibly looking at the rest
+// of the record too. Returns a description of the problem, if any
+pub trait Validator {
+    fn validate(&self, value: &str, record: &[String; Column::SIZE]) -> Result<(), String>;
+}
+
+// The validators used for each column. Empty values are never passed
+// to validators, since whether a value is required is a separate
+// matter
+pub struct Validators {
+    validators: Vec<(Column, Box<dyn Validator>)>,
+}
+
+impl Validators {
+    pub fn new(settings: &Settings) -> Self {
+        let mut ret = Self {
+            validators: Vec::new(),
+        };
+
+        ret.add(Column::ID, Box::new(CodiceFiscale::new(settings.cross_check_codice_fiscale())));
+
+        ret
+    }
+
+    pub fn add(&mut self, column: Column, validator: Box<dyn Validator>) {
+        self.validators.push((column, validator));
+    }
+
+    // Returns the first problem found with the value in the column
+    pub fn validate(&self, column: &Column, record: &[String; Column::SIZE]) -> Option<String> {
+        let index = usize::from(column.clone());
+        let value = record[index].trim();
+
+        if value.is_empty() {
+            return None;
+        }
+
+        self.validators.iter().filter(|(c, _)| usize::from(c.clone()) == index).find_map(|(_, validator)| {
+            validator.validate(value, record).err()
+        })
+    }
+}
@@ -2,26 +2,66 @@
 // Copyright (C) 2017-2021  Andrea Bolognani <eof@kiyuko.org>
 // SPDX-License-Identifier: GPL-2.0-or-later
 
+use std::cell::Ref;
 use std::cell::RefCell;
-use std::cmp;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::cmp::Ordering;
 use std::ffi::OsStr;
-use std::fmt;
+use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
 use std::rc::Rc;
 
+use ::chrono::Datelike;
 use ::gio::prelude::*;
 use ::gtk::prelude::*;
 
+use crate::column;
 use crate::column::Column;
+use crate::clipboard;
+use crate::needle::Needle;
+use crate::person::Person;
+use crate::peopledialog::PeopleDialog;
+use crate::memberdialog::MemberDialog;
+use crate::bulkeditdialog::BulkEditDialog;
+use crate::findreplacedialog::FindReplaceDialog;
+use crate::duplicates;
+use crate::problems;
+use crate::problems::Fix;
+use crate::problems::Problem;
+use crate::validator::Validators;
+use crate::duplicatesdialog::Decision;
+use crate::duplicatesdialog::DuplicatesDialog;
+use crate::signature::Signature;
+use crate::attendance::Attendance;
+use crate::ledger;
+use crate::ledger::Ledger;
+use crate::ledger::Payment;
+use crate::history::Entry;
+use crate::receipt;
+use crate::receipt::Receipt;
+use crate::paymentsdialog::PaymentsDialog;
+use crate::cashsummarydialog::CashSummaryDialog;
+use crate::statsdialog::StatsDialog;
+use crate::signaturedialog::SignatureDialog;
+use crate::cardtemplate::CardTemplate;
+use crate::cardprinter::CardPrinter;
+use crate::report::Report;
 use crate::simpleaction::SimpleAction;
 use crate::simpleactionstateful::SimpleActionStateful;
+use crate::liststore;
 use crate::liststore::ListStore;
+use crate::storage;
+use crate::storage::Storage;
+use crate::sqlitestorage::SqliteStorage;
+use crate::settings::Settings;
 use crate::application::Application;
 
 #[derive(Clone)]
 pub struct Window {
     parent: gtk::ApplicationWindow,
+    application: Application,
     headerbar: gtk::HeaderBar,
     searchbutton: gtk::ToggleButton,
     insertbutton: gtk::Button,
@@ -30,30 +70,78 @@ pub struct Window {
     stack: gtk::Stack,
     searchentry: gtk::SearchEntry,
     searchbar: gtk::SearchBar,
+    savedbutton: gtk::MenuButton,
+    savedpopover: gtk::Popover,
+    savedlist: gtk::ListBox,
+    countlabel: gtk::Label,
+    checkinbar: gtk::InfoBar,
+    checkinlabel: gtk::Label,
+    attendancelabel: gtk::Label,
+    resultsstack: gtk::Stack,
+    problemsrevealer: gtk::Revealer,
+    problemslabel: gtk::Label,
+    problemslist: gtk::ListBox,
     treeview: gtk::TreeView,
     peoplecolumn: gtk::TreeViewColumn,
     searchaction: SimpleAction,
     insertaction: SimpleAction,
     openaction: SimpleAction,
     saveaction: SimpleAction,
+    undoaction: SimpleAction,
+    bulkeditaction: SimpleAction,
+    findreplaceaction: SimpleAction,
+    duplicatesaction: SimpleAction,
+    editpeopleaction: SimpleAction,
+    capturesignatureaction: SimpleAction,
+    printcardsaction: SimpleAction,
+    exportcardsaction: SimpleAction,
+    printreportaction: SimpleAction,
+    exportreportaction: SimpleAction,
+    exportattendanceaction: SimpleAction,
+    paymentsaction: SimpleAction,
+    cashsummaryaction: SimpleAction,
+    statsaction: SimpleAction,
+    sortbyrelevanceaction: SimpleAction,
+    converttosqliteaction: SimpleAction,
+    transferaction: SimpleAction,
+    copyrowsaction: SimpleAction,
+    pasterowsaction: SimpleAction,
     togglesearchaction: SimpleActionStateful,
     togglemenuaction: SimpleActionStateful,
+    fuzzysearchaction: SimpleActionStateful,
+    scanmodeaction: SimpleActionStateful,
+    checkinaction: SimpleActionStateful,
+    problemsaction: SimpleActionStateful,
+    copyheaderaction: SimpleActionStateful,
+    settings: Settings,
     source_filename: Rc<RefCell<PathBuf>>,
+    storage: Rc<RefCell<Option<Box<dyn Storage>>>>,
     source_uri: Rc<RefCell<String>>,
     dirty: Rc<RefCell<bool>>,
+    undo: Rc<RefCell<Vec<Vec<(gtk::TreePath, Column, String)>>>>,
     data: Rc<RefCell<ListStore>>,
     filtered_data: Rc<RefCell<gtk::TreeModelFilter>>,
-    filter_needle: Rc<RefCell<String>>,
+    sorted_data: Rc<RefCell<gtk::TreeModelSort>>,
+    filter_needle: Rc<RefCell<Needle>>,
+    thumbnails: Rc<RefCell<HashMap<String, Option<cairo::ImageSurface>>>>,
+    attendance: Rc<RefCell<Option<Attendance>>>,
+    ledger: Rc<RefCell<Ledger>>,
+    validators: Rc<Validators>,
+    problems: Rc<RefCell<Vec<Problem>>>,
 }
 
 impl Window {
     pub fn new(app: &Application) -> Self {
         let menubutton = gtk::ToggleButton::new();
         let menupopover = gtk::Popover::new(Some(&menubutton));
+        let savedbutton = gtk::MenuButton::new();
+        let savedpopover = gtk::Popover::new(Some(&savedbutton));
         let data = ListStore::new();
         let filtered_data = data.create_filter();
+        let sorted_data = gtk::TreeModelSort::new(&filtered_data);
         let ret = Self {
             parent: app.create_window(),
+            application: app.clone(),
             headerbar: gtk::HeaderBar::new(),
             searchbutton: gtk::ToggleButton::new(),
             insertbutton: gtk::Button::with_label("Insert"),
@@ -62,20 +150,64 @@ impl Window {
             stack: gtk::Stack::new(),
             searchentry: gtk::SearchEntry::new(),
             searchbar: gtk::SearchBar::new(),
+            savedbutton,
+            savedpopover,
+            savedlist: gtk::ListBox::new(),
+            countlabel: gtk::Label::new(None),
+            checkinbar: gtk::InfoBar::new(),
+            checkinlabel: gtk::Label::new(None),
+            attendancelabel: gtk::Label::new(None),
+            resultsstack: gtk::Stack::new(),
+            problemsrevealer: gtk::Revealer::new(),
+            problemslabel: gtk::Label::new(None),
+            problemslist: gtk::ListBox::new(),
             treeview: gtk::TreeView::new(),
             peoplecolumn: gtk::TreeViewColumn::new(),
             searchaction: SimpleAction::new("search"),
             insertaction: SimpleAction::new("insert"),
             openaction: SimpleAction::new("open"),
             saveaction: SimpleAction::new("save"),
+            undoaction: SimpleAction::new("undo"),
+            bulkeditaction: SimpleAction::new("bulkedit"),
+            findreplaceaction: SimpleAction::new("findreplace"),
+            duplicatesaction: SimpleAction::new("duplicates"),
+            editpeopleaction: SimpleAction::new("editpeople"),
+            capturesignatureaction: SimpleAction::new("capturesignature"),
+            printcardsaction: SimpleAction::new("printcards"),
+            exportcardsaction: SimpleAction::new("exportcards"),
+            printreportaction: SimpleAction::new("printreport"),
+            exportreportaction: SimpleAction::new("exportreport"),
+            exportattendanceaction: SimpleAction::new("exportattendance"),
+            paymentsaction: SimpleAction::new("payments"),
+            cashsummaryaction: SimpleAction::new("cashsummary"),
+            statsaction: SimpleAction::new("stats"),
+            sortbyrelevanceaction: SimpleAction::new("sortbyrelevance"),
+            converttosqliteaction: SimpleAction::new("converttosqlite"),
+            transferaction: SimpleAction::new("transfer"),
+            copyrowsaction: SimpleAction::new("copyrows"),
+            pasterowsaction: SimpleAction::new("pasterows"),
             togglesearchaction: SimpleActionStateful::new("togglesearch", false),
             togglemenuaction: SimpleActionStateful::new("togglemenu", false),
+            fuzzysearchaction: SimpleActionStateful::new("fuzzysearch", false),
+            scanmodeaction: SimpleActionStateful::new("scanmode", false),
+            checkinaction: SimpleActionStateful::new("checkin", false),
+            problemsaction: SimpleActionStateful::new("problems", false),
+            copyheaderaction: SimpleActionStateful::new("copyheader", false),
+            settings: app.settings().clone(),
             source_filename: Rc::new(RefCell::new(PathBuf::new())),
+            storage: Rc::new(RefCell::new(None)),
             source_uri: Rc::new(RefCell::new(String::new())),
             dirty: Rc::new(RefCell::new(false)),
+            undo: Rc::new(RefCell::new(Vec::new())),
             data: Rc::new(RefCell::new(data)),
             filtered_data: Rc::new(RefCell::new(filtered_data)),
-            filter_needle: Rc::new(RefCell::new(String::new())),
+            sorted_data: Rc::new(RefCell::new(sorted_data)),
+            filter_needle: Rc::new(RefCell::new(Needle::new("", false))),
+            thumbnails: Rc::new(RefCell::new(HashMap::new())),
+            attendance: Rc::new(RefCell::new(None)),
+            ledger: Rc::new(RefCell::new(Ledger::new(Vec::new()))),
+            validators: Rc::new(Validators::new(app.settings())),
+            problems: Rc::new(RefCell::new(Vec::new())),
         };
         ret.setup();
         ret
@@ -91,6 +223,11 @@ impl Window {
             _self.delete_event()
         });
 
+        let _self = self.clone();
+        self.parent.connect_destroy(move |_| {
+            _self.destroyed();
+        });
+
         /* Actions */
 
         let _self = self.clone();
@@ -120,6 +257,139 @@ impl Window {
         self.saveaction.set_enabled(false);
         self.parent.add_action(self.saveaction.as_parent());
 
+        let _self = self.clone();
+        self.undoaction.as_parent().connect_activate(move |_,_| {
+            _self.undo_action_activated();
+        });
+        self.undoaction.set_enabled(false);
+        self.parent.add_action(self.undoaction.as_parent());
+
+        let _self = self.clone();
+        self.bulkeditaction.as_parent().connect_activate(move |_,_| {
+            _self.bulk_edit_action_activated();
+        });
+        self.bulkeditaction.set_enabled(false);
+        self.parent.add_action(self.bulkeditaction.as_parent());
+
+        let _self = self.clone();
+        self.findreplaceaction.as_parent().connect_activate(move |_,_| {
+            _self.find_replace_action_activated();
+        });
+        self.findreplaceaction.set_enabled(false);
+        self.parent.add_action(self.findreplaceaction.as_parent());
+
+        let _self = self.clone();
+        self.duplicatesaction.as_parent().connect_activate(move |_,_| {
+            _self.duplicates_action_activated();
+        });
+        self.duplicatesaction.set_enabled(false);
+        self.parent.add_action(self.duplicatesaction.as_parent());
+
+        let _self = self.clone();
+        self.editpeopleaction.as_parent().connect_activate(move |_,_| {
+            _self.edit_people_action_activated();
+        });
+        self.editpeopleaction.set_enabled(false);
+        self.parent.add_action(self.editpeopleaction.as_parent());
+
+        let _self = self.clone();
+        self.capturesignatureaction.as_parent().connect_activate(move |_,_| {
+            _self.capture_signature_action_activated();
+        });
+        self.capturesignatureaction.set_enabled(false);
+        self.parent.add_action(self.capturesignatureaction.as_parent());
+
+        let _self = self.clone();
+        self.printcardsaction.as_parent().connect_activate(move |_,_| {
+            _self.print_cards_action_activated();
+        });
+        self.printcardsaction.set_enabled(false);
+        self.parent.add_action(self.printcardsaction.as_parent());
+
+        let _self = self.clone();
+        self.exportcardsaction.as_parent().connect_activate(move |_,_| {
+            _self.export_cards_action_activated();
+        });
+        self.exportcardsaction.set_enabled(false);
+        self.parent.add_action(self.exportcardsaction.as_parent());
+
+        let _self = self.clone();
+        self.printreportaction.as_parent().connect_activate(move |_,_| {
+            _self.print_report_action_activated();
+        });
+        self.printreportaction.set_enabled(false);
+        self.parent.add_action(self.printreportaction.as_parent());
+
+        let _self = self.clone();
+        self.exportreportaction.as_parent().connect_activate(move |_,_| {
+            _self.export_report_action_activated();
+        });
+        self.exportreportaction.set_enabled(false);
+        self.parent.add_action(self.exportreportaction.as_parent());
+
+        let _self = self.clone();
+        self.paymentsaction.as_parent().connect_activate(move |_,_| {
+            _self.payments_action_activated();
+        });
+        self.paymentsaction.set_enabled(false);
+        self.parent.add_action(self.paymentsaction.as_parent());
+
+        let _self = self.clone();
+        self.cashsummaryaction.as_parent().connect_activate(move |_,_| {
+            _self.cash_summary_action_activated();
+        });
+        self.cashsummaryaction.set_enabled(false);
+        self.parent.add_action(self.cashsummaryaction.as_parent());
+
+        let _self = self.clone();
+        self.statsaction.as_parent().connect_activate(move |_,_| {
+            _self.stats_action_activated();
+        });
+        self.statsaction.set_enabled(false);
+        self.parent.add_action(self.statsaction.as_parent());
+
+        let _self = self.clone();
+        self.sortbyrelevanceaction.as_parent().connect_activate(move |_,_| {
+            _self.sort_by_relevance_action_activated();
+        });
+        self.sortbyrelevanceaction.set_enabled(false);
+        self.parent.add_action(self.sortbyrelevanceaction.as_parent());
+
+        let _self = self.clone();
+        self.converttosqliteaction.as_parent().connect_activate(move |_,_| {
+            _self.convert_to_sqlite_action_activated();
+        });
+        self.converttosqliteaction.set_enabled(false);
+        self.parent.add_action(self.converttosqliteaction.as_parent());
+
+        let _self = self.clone();
+        self.transferaction.as_parent().connect_activate(move |_,_| {
+            _self.transfer_action_activated();
+        });
+        self.transferaction.set_enabled(false);
+        self.parent.add_action(self.transferaction.as_parent());
+
+        let _self = self.clone();
+        self.copyrowsaction.as_parent().connect_activate(move |_,_| {
+            _self.copy_rows_action_activated();
+        });
+        self.copyrowsaction.set_enabled(false);
+        self.parent.add_action(self.copyrowsaction.as_parent());
+
+        let _self = self.clone();
+        self.pasterowsaction.as_parent().connect_activate(move |_,_| {
+            _self.paste_rows_action_activated();
+        });
+        self.pasterowsaction.set_enabled(false);
+        self.parent.add_action(self.pasterowsaction.as_parent());
+
+        let _self = self.clone();
+        self.exportattendanceaction.as_parent().connect_activate(move |_,_| {
+            _self.export_attendance_action_activated();
+        });
+        self.exportattendanceaction.set_enabled(false);
+        self.parent.add_action(self.exportattendanceaction.as_parent());
+
         let _self = self.clone();
         self.togglesearchaction.as_parent().connect_activate(move |_,_| {
             _self.toggle_search_action_activated();
@@ -133,6 +403,39 @@ impl Window {
         });
         self.parent.add_action(self.togglemenuaction.as_parent());
 
+        let _self = self.clone();
+        self.fuzzysearchaction.as_parent().connect_activate(move |_,_| {
+            _self.fuzzy_search_action_activated();
+        });
+        self.parent.add_action(self.fuzzysearchaction.as_parent());
+
+        let _self = self.clone();
+        self.scanmodeaction.as_parent().connect_activate(move |_,_| {
+            _self.scan_mode_action_activated();
+        });
+        self.scanmodeaction.set_enabled(false);
+        self.parent.add_action(self.scanmodeaction.as_parent());
+
+        let _self = self.clone();
+        self.checkinaction.as_parent().connect_activate(move |_,_| {
+            _self.check_in_action_activated();
+        });
+        self.checkinaction.set_enabled(false);
+        self.parent.add_action(self.checkinaction.as_parent());
+
+        let _self = self.clone();
+        self.problemsaction.as_parent().connect_activate(move |_,_| {
+            _self.problems_action_activated();
+        });
+        self.problemsaction.set_enabled(false);
+        self.parent.add_action(self.problemsaction.as_parent());
+
+        let _self = self.clone();
+        self.copyheaderaction.as_parent().connect_activate(move |_,_| {
+            _self.copy_header_action_activated();
+        });
+        self.parent.add_action(self.copyheaderaction.as_parent());
+
         /* Header bar */
 
         self.headerbar.set_show_close_button(true);
@@ -160,8 +463,33 @@ impl Window {
         self.headerbar.pack_end(&self.menubutton);
 
         let menu = gio::Menu::new();
+        menu.append(Some("New window"), Some("app.newwindow"));
         menu.append(Some("Open"), Some("win.open"));
         menu.append(Some("Save"), Some("win.save"));
+        menu.append(Some("Convert to SQLite"), Some("win.converttosqlite"));
+        menu.append(Some("Undo"), Some("win.undo"));
+        menu.append(Some("Edit people"), Some("win.editpeople"));
+        menu.append(Some("Set field…"), Some("win.bulkedit"));
+        menu.append(Some("Find and replace…"), Some("win.findreplace"));
+        menu.append(Some("Find possible duplicates"), Some("win.duplicates"));
+        menu.append(Some("Problems"), Some("win.problems"));
+        menu.append(Some("Capture signature"), Some("win.capturesignature"));
+        menu.append(Some("Copy rows"), Some("win.copyrows"));
+        menu.append(Some("Paste rows"), Some("win.pasterows"));
+        menu.append(Some("Copy with header"), Some("win.copyheader"));
+        menu.append(Some("Move/Copy to registry…"), Some("win.transfer"));
+        menu.append(Some("Print cards"), Some("win.printcards"));
+        menu.append(Some("Export cards to PDF"), Some("win.exportcards"));
+        menu.append(Some("Payments"), Some("win.payments"));
+        menu.append(Some("Cash summary"), Some("win.cashsummary"));
+        menu.append(Some("Statistics"), Some("win.stats"));
+        menu.append(Some("Print registry"), Some("win.printreport"));
+        menu.append(Some("Export registry to PDF"), Some("win.exportreport"));
+        menu.append(Some("Sort by relevance"), Some("win.sortbyrelevance"));
+        menu.append(Some("Fuzzy search"), Some("win.fuzzysearch"));
+        menu.append(Some("Scan mode"), Some("win.scanmode"));
+        menu.append(Some("Check-in mode"), Some("win.checkin"));
+        menu.append(Some("Export attendance"), Some("win.exportattendance"));
         self.menupopover.bind_model(Some(&menu), None);
         self.menupopover.set_relative_to(Some(&self.menubutton));
 
@@ -188,10 +516,88 @@ impl Window {
             _self.stop_search();
         });
 
+        let _self = self.clone();
+        self.searchentry.connect_activate(move |_| {
+            _self.search_activated();
+        });
+
+        let _self = self.clone();
+        self.searchentry.connect_key_press_event(move |_, event| {
+            _self.search_key_pressed(event)
+        });
+
+        let image = gtk::Image::from_icon_name(
+            Some("starred-symbolic"),
+            gtk::IconSize::Button,
+        );
+        self.savedbutton.set_image(Some(&image));
+        self.savedbutton.set_tooltip_text(Some("Saved searches"));
+        self.savedbutton.set_popover(Some(&self.savedpopover));
+
+        self.savedlist.set_selection_mode(gtk::SelectionMode::None);
+        let _self = self.clone();
+        self.savedlist.connect_row_activated(move |_, row| {
+            _self.saved_search_activated(row.index());
+        });
+
+        let savebutton = gtk::Button::with_label("Save current search");
+        let _self = self.clone();
+        savebutton.connect_clicked(move |_| {
+            _self.save_search_clicked();
+        });
+
+        let savedbox = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        savedbox.set_border_width(6);
+        savedbox.pack_start(&self.savedlist, false, false, 0);
+        savedbox.pack_start(&savebutton, false, false, 0);
+        savedbox.show_all();
+        self.savedpopover.add(&savedbox);
+
+        // Match counts are refreshed every time the popover is
+        // displayed, so that they always reflect the current data
+        let _self = self.clone();
+        self.savedpopover.connect_show(move |_| {
+            _self.update_saved_searches();
+        });
+
+        let searchbox = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        searchbox.pack_start(&self.searchentry, false, false, 0);
+        searchbox.pack_start(&self.savedbutton, false, false, 0);
+        searchbox.pack_start(&self.countlabel, false, false, 0);
+
+        self.countlabel.style_context().add_class("dim-label");
+
         self.searchbar.connect_entry(&self.searchentry);
-        self.searchbar.add(&self.searchentry);
+        self.searchbar.add(&searchbox);
+
+        /* Check-in mode */
+
+        self.checkinlabel.set_xalign(0.0);
+        self.checkinlabel.set_line_wrap(true);
+        self.attendancelabel.set_xalign(1.0);
+        self.attendancelabel.set_hexpand(true);
+        let checkinbox = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        checkinbox.pack_start(&self.checkinlabel, true, true, 0);
+        checkinbox.pack_start(&self.attendancelabel, false, false, 0);
+        self.checkinbar.content_area().add(&checkinbox);
+        self.checkinbar.set_no_show_all(true);
+        checkinbox.show_all();
 
         self.treeview.set_enable_search(false);
+        self.treeview.selection().set_mode(gtk::SelectionMode::Multiple);
+
+        // Rows are copied and pasted as a whole only while the focus
+        // is on the treeview, so that the usual shortcuts keep working
+        // on text in the search entry and in the cell being edited
+        let _self = self.clone();
+        self.treeview.connect_key_press_event(move |_, event| {
+            _self.treeview_key_pressed(event)
+        });
+
+        let _self = self.clone();
+        self.treeview.connect_row_activated(move |_, path, _| {
+            _self.row_activated(path.clone());
+        });
 
         let number_renderer = gtk::CellRendererText::new();
         CellRendererExt::set_alignment(&number_renderer, 1.0, 0.5);
@@ -201,6 +607,8 @@ impl Window {
             _self.number_cell_edited(path, text);
         });
         let column = gtk::TreeViewColumn::new();
+        column.set_title("Number");
+        column.set_sort_column_id(Column::Number.into());
         column.pack_start(&number_renderer, false);
         column.add_attribute(&number_renderer, "text", Column::Number.into());
         self.treeview.append_column(&column);
@@ -214,8 +622,12 @@ impl Window {
         });
         self.peoplecolumn.set_title("People");
         self.peoplecolumn.set_expand(true);
+        self.peoplecolumn.set_sort_column_id(Column::People.into());
         self.peoplecolumn.pack_start(&people_renderer, false);
-        self.peoplecolumn.add_attribute(&people_renderer, "text", Column::People.into());
+        let _self = self.clone();
+        TreeViewColumnExt::set_cell_data_func(&self.peoplecolumn, &people_renderer, Some(Box::new(move |_, cell, model, iter| {
+            _self.highlight_cell_data_func(cell, model, iter, &Column::People);
+        })));
         self.treeview.append_column(&self.peoplecolumn);
 
         let signature_renderer = gtk::CellRendererText::new();
@@ -224,11 +636,21 @@ impl Window {
         signature_renderer.connect_edited(move |_, path, text| {
             _self.signature_cell_edited(path, text);
         });
+        let thumbnail_renderer = gtk::CellRendererPixbuf::new();
         let column = gtk::TreeViewColumn::new();
         column.set_title("Signature");
+        column.set_sort_column_id(Column::Signature.into());
         column.set_sizing(gtk::TreeViewColumnSizing::GrowOnly);
+        column.pack_start(&thumbnail_renderer, false);
+        let _self = self.clone();
+        TreeViewColumnExt::set_cell_data_func(&column, &thumbnail_renderer, Some(Box::new(move |_, cell, model, iter| {
+            _self.thumbnail_cell_data_func(cell, model, iter);
+        })));
         column.pack_start(&signature_renderer, false);
-        column.add_attribute(&signature_renderer, "text", Column::Signature.into());
+        let _self = self.clone();
+        TreeViewColumnExt::set_cell_data_func(&column, &signature_renderer, Some(Box::new(move |_, cell, model, iter| {
+            _self.highlight_cell_data_func(cell, model, iter, &Column::Signature);
+        })));
         self.treeview.append_column(&column);
 
         let id_renderer = gtk::CellRendererText::new();
@@ -239,9 +661,13 @@ impl Window {
         });
         let column = gtk::TreeViewColumn::new();
         column.set_title("ID");
+        column.set_sort_column_id(Column::ID.into());
         column.set_sizing(gtk::TreeViewColumnSizing::GrowOnly);
         column.pack_start(&id_renderer, false);
-        column.add_attribute(&id_renderer, "text", Column::ID.into());
+        let _self = self.clone();
+        TreeViewColumnExt::set_cell_data_func(&column, &id_renderer, Some(Box::new(move |_, cell, model, iter| {
+            _self.validated_cell_data_func(cell, model, iter, &Column::ID);
+        })));
         self.treeview.append_column(&column);
 
         let date_renderer = gtk::CellRendererText::new();
@@ -252,17 +678,82 @@ impl Window {
         });
         let column = gtk::TreeViewColumn::new();
         column.set_title("Date");
+        column.set_sort_column_id(Column::Date.into());
         column.pack_start(&date_renderer, false);
         column.add_attribute(&date_renderer, "text", Column::Date.into());
         self.treeview.append_column(&column);
 
+        // Payments are not part of the registry itself, so these
+        // columns are computed from the ledger when drawing
+        let balance_renderer = gtk::CellRendererText::new();
+        CellRendererExt::set_alignment(&balance_renderer, 1.0, 0.5);
+        let column = gtk::TreeViewColumn::new();
+        column.set_title("Balance");
+        column.pack_start(&balance_renderer, false);
+        let _self = self.clone();
+        TreeViewColumnExt::set_cell_data_func(&column, &balance_renderer, Some(Box::new(move |_, cell, model, iter| {
+            _self.balance_cell_data_func(cell, model, iter);
+        })));
+        self.treeview.append_column(&column);
+
+        let last_paid_renderer = gtk::CellRendererText::new();
+        let column = gtk::TreeViewColumn::new();
+        column.set_title("Last paid");
+        column.pack_start(&last_paid_renderer, false);
+        let _self = self.clone();
+        TreeViewColumnExt::set_cell_data_func(&column, &last_paid_renderer, Some(Box::new(move |_, cell, model, iter| {
+            _self.last_paid_cell_data_func(cell, model, iter);
+        })));
+        self.treeview.append_column(&column);
+
 
         let auto_adj: Option<&gtk::Adjustment> = None;
         let scrolled = gtk::ScrolledWindow::new(auto_adj, auto_adj);
         scrolled.add(&self.treeview);
 
+        /* No search results */
+
+        let image = gtk::Image::from_icon_name(
+            Some("edit-find-symbolic"),
+            gtk::IconSize::Dialog,
+        );
+        image.set_pixel_size(64);
+        let label = gtk::Label::new(Some("No matching members"));
+        let placeholder = gtk::Box::new(gtk::Orientation::Vertical, 12);
+        placeholder.set_valign(gtk::Align::Center);
+        placeholder.style_context().add_class("dim-label");
+        placeholder.pack_start(&image, false, false, 0);
+        placeholder.pack_start(&label, false, false, 0);
+
+        self.resultsstack.add_named(&scrolled, "results");
+        self.resultsstack.add_named(&placeholder, "placeholder");
+
+        /* Problems */
+
+        self.problemslabel.set_xalign(0.0);
+        self.problemslabel.set_margin_start(6);
+        self.problemslabel.set_margin_top(6);
+
+        let _self = self.clone();
+        self.problemslist.connect_row_activated(move |_, row| {
+            _self.problem_activated(row.index());
+        });
+
+        let auto_adj: Option<&gtk::Adjustment> = None;
+        let problemsscrolled = gtk::ScrolledWindow::new(auto_adj, auto_adj);
+        problemsscrolled.set_size_request(-1, 150);
+        problemsscrolled.add(&self.problemslist);
+
+        let problemsbox = gtk::Box::new(gtk::Orientation::Vertical, 6);
+        problemsbox.pack_start(&gtk::Separator::new(gtk::Orientation::Horizontal), false, false, 0);
+        problemsbox.pack_start(&self.problemslabel, false, false, 0);
+        problemsbox.pack_start(&problemsscrolled, true, true, 0);
+        self.problemsrevealer.add(&problemsbox);
+
         contents.pack_start(&self.searchbar, false, false, 0);
-        contents.pack_start(&scrolled, true, true, 0);
+        contents.pack_start(&self.checkinbar, false, false, 0);
+        contents.pack_start(&self.resultsstack, true, true, 0);
+        contents.pack_start(&self.problemsrevealer, false, false, 0);
 
         self.stack.add_named(&empty, "empty");
         self.stack.add_named(&contents, "contents");
@@ -273,6 +764,93 @@ impl Window {
         self.parent.show_all();
     }
 
+    pub fn present(&self) {
+        self.parent.present();
+    }
+
+    pub fn as_parent(&self) -> &gtk::ApplicationWindow {
+        &self.parent
+    }
+
+    pub fn source_filename(&self) -> PathBuf {
+        self.source_filename.borrow().clone()
+    }
+
+    pub fn has_document(&self) -> bool {
+        !self.source_filename.borrow().as_os_str().is_empty()
+    }
+
+    // Used to refer to the document when talking to the user
+    pub fn document_name(&self) -> String {
+        self.source_filename.borrow().file_name().map_or(String::new(), |name| {
+            name.to_string_lossy().to_string()
+        })
+    }
+
+    // All records, including unsaved changes
+    pub fn records(&self) -> Vec<[String; Column::SIZE]> {
+        self.all_paths().iter().map(|path| self.record(path)).collect()
+    }
+
+    // Records coming from another registry are saved right away, same
+    // as when the registry is not open, so that they always match its
+    // history. The registry is expected not to have unsaved changes
+    pub fn receive_records(&self, records: &[[String; Column::SIZE]]) {
+        self.append_records(records);
+        self.write_data();
+    }
+
+    // Records are added as unsaved changes, same as if they had been
+    // inserted by hand
+    fn append_records(&self, records: &[[String; Column::SIZE]]) {
+        {
+            let data: &ListStore = &*self.data.borrow();
+
+            for values in records {
+                let iter = data.append();
+                data.set_all_values(&iter, values);
+            }
+        }
+
+        self.set_dirty(true);
+        self.update_problems();
+    }
+
+    // Nothing changes unless the whole document can be read, so that
+    // it's never possible to save over data that failed to load
+    pub fn open(&self, filename: PathBuf, uri: String) {
+        let storage = storage::open(&filename);
+        let result = storage.load().and_then(|records| {
+            Ok((records, storage.load_payments()?))
+        });
+
+        match result {
+            Ok((records, payments)) => {
+                self.set_data_source(filename, uri);
+                self.load_data(&records, payments);
+            },
+            Err(e) => {
+                let name = filename.file_name().map_or(String::new(), |name| {
+                    name.to_string_lossy().to_string()
+                });
+                self.show_error(&format!("Failed to open {}: {}", name, e));
+            },
+        }
+    }
+
+    // Returns false if the user has cancelled the operation
+    pub fn save(&self) -> bool {
+        self.save_data()
+    }
+
+    // Closes the window without asking about unsaved changes, which
+    // is expected to have happened already
+    pub fn close(&self) {
+        unsafe {
+            self.parent.destroy();
+        }
+    }
+
     fn update_title(&self) {
         let source_filename: &PathBuf = &*self.source_filename.borrow();
 
@@ -294,6 +872,10 @@ impl Window {
     }
 
     fn set_data_source(&self, filename: PathBuf, uri: String) {
+        {
+            let mut storage = self.storage.borrow_mut();
+            *storage = Some(storage::open(&filename));
+        }
         {
             let mut source_filename = self.source_filename.borrow_mut();
             *source_filename = filename;
@@ -306,6 +888,12 @@ impl Window {
         self.update_title();
     }
 
+    // Only used once a document has been loaded, since all actions
+    // that need it are disabled until then
+    fn storage(&self) -> Ref<Box<dyn Storage>> {
+        Ref::map(self.storage.borrow(), |storage| storage.as_ref().unwrap())
+    }
+
     fn set_dirty(&self, dirty: bool) {
         *self.dirty.borrow_mut() = dirty;
         self.saveaction.set_enabled(dirty);
@@ -313,68 +901,191 @@ impl Window {
         self.update_title()
     }
 
-    fn is_dirty(&self) -> bool {
+    pub fn is_dirty(&self) -> bool {
         *self.dirty.borrow()
     }
 
     fn search(&self) {
         {
             let mut filter_needle = self.filter_needle.borrow_mut();
-            *filter_needle = self.searchentry.text().to_lowercase();
+            *filter_needle = Needle::new(
+                &self.searchentry.text(),
+                self.fuzzysearchaction.state(),
+            );
         }
 
         let filtered_data: &gtk::TreeModelFilter = &*self.filtered_data.borrow();
+        let sorted_data: &gtk::TreeModelSort = &*self.sorted_data.borrow();
         filtered_data.refilter();
+
+        // Rows that were already visible keep their position when
+        // the filter changes, so we have to force a full sort in
+        // order for the ranking to be updated. Sorting explicitly
+        // requested by the user takes precedence over ranking
+        if let Some((gtk::SortColumn::Default, _)) = sorted_data.sort_column_id() {
+            sorted_data.set_unsorted();
+            sorted_data.set_sort_column_id(gtk::SortColumn::Default, gtk::SortType::Ascending);
+        }
+
+        // Highlighting depends on the needle, so rows that were
+        // visible before need to be drawn again
+        self.treeview.queue_draw();
+
+        self.update_search_status();
+    }
+
+    fn update_search_status(&self) {
+        let data: &ListStore = &*self.data.borrow();
+        let sorted_data: &gtk::TreeModelSort = &*self.sorted_data.borrow();
+        let filter_needle: &Needle = &*self.filter_needle.borrow();
+
+        let total = data.len();
+        let matches = sorted_data.iter_n_children(None);
+
+        if filter_needle.is_empty() {
+            self.countlabel.set_text("");
+        } else {
+            self.countlabel.set_text(&format!("{} of {} members", matches, total));
+        }
+
+        // Show a placeholder instead of an empty list, unless the
+        // document itself is empty
+        if matches == 0 && total > 0 {
+            self.resultsstack.set_visible_child_name("placeholder");
+        } else {
+            self.resultsstack.set_visible_child_name("results");
+        }
+    }
+
+    // Moves the cursor to the n-th visible record, wrapping around
+    // at both ends; returns false if there are no visible records
+    fn select_match(&self, n: i32) -> bool {
+        let sorted_data: &gtk::TreeModelSort = &*self.sorted_data.borrow();
+        let matches = sorted_data.iter_n_children(None);
+
+        if matches == 0 {
+            return false;
+        }
+
+        let path = gtk::TreePath::from_indicesv(&[n.rem_euclid(matches)]);
+        self.treeview.set_cursor(&path, None::<&gtk::TreeViewColumn>, false);
+        self.treeview.scroll_to_cell(Some(&path), None::<&gtk::TreeViewColumn>, false, 0.0, 0.0);
+
+        true
+    }
+
+    // Returns the position of the cursor among visible records
+    fn selected_match(&self) -> Option<i32> {
+        let (path, _) = self.treeview.cursor();
+
+        path.and_then(|path| path.indices().first().copied())
     }
 
-    fn load_data(&self) {
+    fn load_data(&self, records: &[[String; Column::SIZE]], payments: Vec<Payment>) {
         {
             let mut data = self.data.borrow_mut();
             let mut filtered_data = self.filtered_data.borrow_mut();
+            let mut sorted_data = self.sorted_data.borrow_mut();
             let mut filter_needle = self.filter_needle.borrow_mut();
 
             *data = ListStore::new();
             *filtered_data = data.create_filter();
-            *filter_needle = String::new();
+            *sorted_data = gtk::TreeModelSort::new(&*filtered_data);
+            *filter_needle = Needle::new("", self.fuzzysearchaction.state());
         }
+        self.thumbnails.borrow_mut().clear();
+        self.clear_undo();
+        self.stop_check_in_action();
 
         let data: &ListStore = &*self.data.borrow();
         let filtered_data: &gtk::TreeModelFilter = &*self.filtered_data.borrow();
-        let path: &PathBuf = &*self.source_filename.borrow();
+        let sorted_data: &gtk::TreeModelSort = &*self.sorted_data.borrow();
 
-        let mut reader = csv::ReaderBuilder::new()
-                         .has_headers(false)
-                         .from_path(path)
-                         .expect("Failed to open file");
-
-        // Grab the good records only
-        let records = reader.records().filter(|r| r.is_ok()).flatten();
-
-        for record in records {
-            let mut values = ListStore::new_row();
-
-            // Extract values from the record. Missing fields default to
-            // the empty string, so that it's possible to load files
-            // created using older versions of the application
-            for i in 0..record.len() {
-                values[i] = String::from(&record[i]);
-            }
+        *self.ledger.borrow_mut() = Ledger::new(payments);
 
+        for values in records {
             let iter = data.append();
-            data.set_all_values(&iter, &values);
+            data.set_all_values(&iter, values);
         }
 
         self.set_dirty(false);
         self.searchaction.set_enabled(true);
         self.insertaction.set_enabled(true);
+        self.editpeopleaction.set_enabled(true);
+        self.bulkeditaction.set_enabled(true);
+        self.findreplaceaction.set_enabled(true);
+        self.duplicatesaction.set_enabled(true);
+        self.problemsaction.set_enabled(true);
+        self.capturesignatureaction.set_enabled(true);
+        self.printcardsaction.set_enabled(true);
+        self.exportcardsaction.set_enabled(true);
+        self.printreportaction.set_enabled(true);
+        self.exportreportaction.set_enabled(true);
         self.togglesearchaction.set_enabled(true);
+        self.scanmodeaction.set_enabled(true);
+        self.checkinaction.set_enabled(true);
+        self.paymentsaction.set_enabled(true);
+        self.cashsummaryaction.set_enabled(true);
+        self.statsaction.set_enabled(true);
+        self.sortbyrelevanceaction.set_enabled(true);
+        self.converttosqliteaction.set_enabled(!storage::is_sqlite(&self.source_filename()));
+        self.transferaction.set_enabled(true);
+        self.copyrowsaction.set_enabled(true);
+        self.pasterowsaction.set_enabled(true);
 
         let _self = self.clone();
         filtered_data.set_visible_func(move |_, iter| {
             _self.filter_func(iter)
         });
-        self.treeview.set_model(Some(filtered_data));
 
+        let _self = self.clone();
+        sorted_data.set_default_sort_func(move |model, a, b| {
+            _self.rank_func(model, a, b)
+        });
+
+        for i in 0..Column::SIZE {
+            let column = Column::from(i);
+            let sort_column = gtk::SortColumn::Index(u32::from(column.clone()));
+
+            let _self = self.clone();
+            sorted_data.set_sort_func(sort_column, move |model, a, b| {
+                _self.sort_func(model, a, b, &column)
+            });
+        }
+
+        // Restore the sort order the user has picked last time they
+        // worked on this document, if any
+        match self.settings.sort_order(&*self.source_uri.borrow()) {
+            Some((column, order)) => {
+                let sort_column = gtk::SortColumn::Index(u32::from(column));
+                sorted_data.set_sort_column_id(sort_column, order);
+            },
+            None => {
+                sorted_data.set_sort_column_id(gtk::SortColumn::Default, gtk::SortType::Ascending);
+            },
+        }
+
+        let _self = self.clone();
+        sorted_data.connect_sort_column_changed(move |_| {
+            _self.sort_column_changed();
+        });
+
+        // Records come and go, or start and stop matching the search,
+        // as a result of many different actions
+        let _self = self.clone();
+        sorted_data.connect_row_inserted(move |_, _, _| {
+            _self.update_search_status();
+        });
+
+        let _self = self.clone();
+        sorted_data.connect_row_deleted(move |_, _| {
+            _self.update_search_status();
+        });
+
+        self.treeview.set_model(Some(sorted_data));
+
+        self.update_search_status();
+        self.update_problems();
         self.stack.set_visible_child_name("contents");
 
         if let Some(recents) = gtk::RecentManager::default() {
@@ -382,83 +1093,348 @@ impl Window {
         }
     }
 
-    fn save_data(&self) {
+    // Records without people are usually left behind by an insert
+    // that's been abandoned, but they might also be the result of a
+    // name having been cleared by mistake, so the user gets to decide
+    // whether they should be kept, incomplete as they are, or removed.
+    // Returns false if the user has cancelled the operation
+    fn check_incomplete_records(&self) -> bool {
+        let paths: Vec<gtk::TreePath> = self.all_paths().into_iter().filter(|path| {
+            self.record(path)[usize::from(Column::People)].trim().is_empty()
+        }).collect();
+
+        if paths.is_empty() {
+            return true;
+        }
+
+        let dialog = gtk::MessageDialog::new(
+            Some(&self.parent),
+            gtk::DialogFlags::MODAL,
+            gtk::MessageType::Question,
+            gtk::ButtonsType::None,
+            &match paths.len() {
+                1 => String::from("1 record has no people"),
+                n => format!("{} records have no people", n),
+            },
+        );
+        dialog.set_secondary_text(Some(
+            "They can be saved as they are, even though they're incomplete, or removed from the registry.",
+        ));
+        dialog.add_button("Cancel", gtk::ResponseType::Cancel);
+        dialog.add_button("Remove", gtk::ResponseType::Reject);
+        dialog.add_button("Keep", gtk::ResponseType::Accept);
+        dialog.set_default_response(gtk::ResponseType::Accept);
 
-        let path: &PathBuf = &*self.source_filename.borrow();
-        let mut writer = csv::WriterBuilder::new()
-                         .has_headers(false)
-                         .from_path(path)
-                         .expect("Failed to open output file");
+        let response = dialog.run();
 
-        let data: &ListStore = &*self.data.borrow();
-        let iter: gtk::TreeIter = data.iter_first().unwrap();
+        unsafe {
+            dialog.destroy();
+        }
+
+        match response {
+            gtk::ResponseType::Accept => true,
+            gtk::ResponseType::Reject => {
+                self.remove_records(paths);
+                true
+            },
+            _ => false,
+        }
+    }
 
-        loop {
-            let mut record = csv::StringRecord::new();
+    // All records are written, so that what's displayed always
+    // matches what's been saved. Returns false if the user has
+    // cancelled the operation
+    fn save_data(&self) -> bool {
+        if !self.check_incomplete_records() {
+            return false;
+        }
 
-            for x in 0..Column::SIZE {
-                let value: Option<String> = data.value(&iter, &Column::from(x));
+        self.write_data();
 
-                match value {
-                    Some(ref field) => record.push_field(field),
-                    None => break,
-                }
-            }
+        true
+    }
 
-            if !&record[Column::People.into()].is_empty() {
-                writer.write_record(&record).expect("Failed to write output file");
-            }
+    // Same as save_data(), without asking the user anything
+    fn write_data(&self) {
+        let records = self.records();
 
-            if !data.iter_next(&iter) { break; }
+        {
+            let storage: &dyn Storage = &**self.storage();
+            storage.save(&records).expect("Failed to write output file");
         }
 
-        writer.flush().expect("Failed to write output file");
-
+        self.move_signatures();
         self.set_dirty(false);
     }
 
-    fn filter_func(&self, iter: &gtk::TreeIter) -> bool {
-        let filter_needle: &String = &*self.filter_needle.borrow();
+    // Signatures are stored under the card number records had when the
+    // document was last saved, so that they keep matching the stored
+    // records until changes to their numbers are saved too. At that
+    // point, they're moved over to the new numbers
+    fn move_signatures(&self) {
+        let mut moves = Vec::new();
+        let mut numbers = HashSet::new();
+        let mut kept = HashSet::new();
 
-        if filter_needle.parse::<i32>().is_ok() {
-            // If the needle can be converted to a number, we look up
-            // the corresponding record
-            self.value_matches(iter, &Column::Number, filter_needle)
-        } else {
-            // In all other cases, we perform a case-insensitive substring
-            // search among people's names and signatures
-            self.value_contains(iter, &Column::People, filter_needle) ||
-            self.value_contains(iter, &Column::Signature, filter_needle)
+        {
+            let data: &ListStore = &*self.data.borrow();
+
+            for path in self.all_paths() {
+                let iter: gtk::TreeIter = data.iter(&path).unwrap();
+                let key = data.key(&iter);
+                let number = data.value(&iter, &Column::Number).unwrap_or_default();
+
+                if key != number {
+                    moves.push((key, number.clone()));
+                } else {
+                    kept.insert(number.clone());
+                }
+                data.set_key(&iter, &number);
+                numbers.insert(number);
+            }
         }
-    }
 
-    fn value_matches(&self, iter: &gtk::TreeIter, column: &Column, needle: &str) -> bool {
-        let data: &ListStore = &*self.data.borrow();
-        data.value(iter, column).map_or(false, |value| {
-            value == needle
-        })
+        if moves.is_empty() {
+            return;
+        }
+
+        // A signature stored under the new number belongs to a record
+        // which has kept its number, if there's one, and can't be
+        // replaced. Numbers might have been swapped, or shifted along,
+        // so all signatures are read before any of them is written
+        let moves: Vec<(&str, &str, Option<Signature>)> = moves.iter().filter(|(_, number)| {
+            !kept.contains(number)
+        }).map(|(key, number)| {
+            (key.as_str(), number.as_str(), self.load_signature(key))
+        }).collect();
+
+        let result = {
+            let storage: &dyn Storage = &**self.storage();
+            let mut result = Ok(());
+
+            for (key, _, _) in &moves {
+                if !numbers.contains(*key) {
+                    result = result.and_then(|_| storage.save_signature(key, &Signature::new()));
+                }
+            }
+            for (_, number, signature) in &moves {
+                let signature = signature.clone().unwrap_or_else(Signature::new);
+                result = result.and_then(|_| storage.save_signature(number, &signature));
+            }
+
+            result
+        };
+
+        self.thumbnails.borrow_mut().clear();
+        self.treeview.queue_draw();
+
+        if let Err(e) = result {
+            self.show_error(&format!("Failed to move signatures: {}", e));
+        }
     }
 
-    fn value_contains(&self, iter: &gtk::TreeIter, column: &Column, needle: &str) -> bool {
-        let data: &ListStore = &*self.data.borrow();
+    fn filter_func(&self, iter: &gtk::TreeIter) -> bool {
+        let filter_needle: &Needle = &*self.filter_needle.borrow();
 
-        data.value(iter, column).map_or(false, |value| {
-            let value = value.to_lowercase();
-
-            // Most entries are in the form
-            //
-            //   LastName FirstName, OtherFirstName
-            //
-            // to save on typing.
-            //
-            // We want such an entry to match when searching for
-            // "LastName OtherFirstName", and in order to do that we
-            // have to split the needle into chunks and check whether
-            // all of them are contained in the entry
-            needle.split_whitespace().all(|chunk| {
-                value.contains(chunk)
-            })
-        })
+        self.score(iter, filter_needle).is_some()
+    }
+
+    // Sorts visible rows so that the best matches come first. Ties,
+    // which are the only possible outcome unless fuzzy search is
+    // enabled, are broken by using the order of the underlying data
+    fn rank_func(&self, model: &gtk::TreeModel, a: &gtk::TreeIter, b: &gtk::TreeIter) -> Ordering {
+        let filter_needle: &Needle = &*self.filter_needle.borrow();
+
+        let mut ret = Ordering::Equal;
+
+        if filter_needle.is_fuzzy() {
+            let filtered_data: &gtk::TreeModelFilter = &*self.filtered_data.borrow();
+            let a_score = self.score(&filtered_data.convert_iter_to_child_iter(a), filter_needle);
+            let b_score = self.score(&filtered_data.convert_iter_to_child_iter(b), filter_needle);
+
+            ret = a_score.cmp(&b_score);
+        }
+
+        ret.then_with(|| model.path(a).cmp(&model.path(b)))
+    }
+
+    fn sort_func(&self, model: &gtk::TreeModel, a: &gtk::TreeIter, b: &gtk::TreeIter, column: &Column) -> Ordering {
+        let a_value: String = model.value(a, column.clone().into()).get().unwrap_or_default();
+        let b_value: String = model.value(b, column.clone().into()).get().unwrap_or_default();
+
+        // Records that compare as equal are kept in the same order
+        // as the underlying data
+        column.compare(&a_value, &b_value).then_with(|| {
+            model.path(a).cmp(&model.path(b))
+        })
+    }
+
+    // Returns None if the record doesn't match the needle, or a
+    // score otherwise: the lower the score, the better the match
+    fn score(&self, iter: &gtk::TreeIter, needle: &Needle) -> Option<usize> {
+        let data: &ListStore = &*self.data.borrow();
+
+        needle.score_record(&data.record(iter))
+    }
+
+    // Used for columns that are searched through, so that the parts
+    // of each value which match the current needle stand out
+    fn highlight_cell_data_func(&self, cell: &gtk::CellRenderer, model: &gtk::TreeModel, iter: &gtk::TreeIter, column: &Column) {
+        let filter_needle: &Needle = &*self.filter_needle.borrow();
+        let value: String = model.value(iter, column.clone().into()).get().unwrap_or_default();
+
+        if let Some(cell) = cell.downcast_ref::<gtk::CellRendererText>() {
+            // Numeric needles are matched against the Number column
+            // rather than names and signatures
+            if filter_needle.as_number().is_some() {
+                cell.set_text(Some(&value));
+            } else {
+                cell.set_markup(Some(&filter_needle.highlight(&value)));
+            }
+        }
+    }
+
+    // Values that don't pass validation stand out, and the problem
+    // is described in the problems panel
+    fn validated_cell_data_func(&self, cell: &gtk::CellRenderer, model: &gtk::TreeModel, iter: &gtk::TreeIter, column: &Column) {
+        let mut record = ListStore::new_row();
+        for (i, value) in record.iter_mut().enumerate() {
+            *value = model.value(iter, i as i32).get().unwrap_or_default();
+        }
+        let value = &record[usize::from(column.clone())];
+
+        if let Some(cell) = cell.downcast_ref::<gtk::CellRendererText>() {
+            if self.validators.validate(column, &record).is_some() {
+                cell.set_markup(Some(&format!("<span foreground='red'>{}</span>", glib::markup_escape_text(value))));
+            } else {
+                cell.set_text(Some(value));
+            }
+        }
+    }
+
+    fn thumbnail_cell_data_func(&self, cell: &gtk::CellRenderer, model: &gtk::TreeModel, iter: &gtk::TreeIter) {
+        let key: String = model.value(iter, liststore::KEY as i32).get().unwrap_or_else(|_| {
+            model.value(iter, Column::Number.into()).get().unwrap_or_default()
+        });
+
+        if let Some(cell) = cell.downcast_ref::<gtk::CellRendererPixbuf>() {
+            let thumbnail = self.signature_thumbnail(&key);
+            cell.set_surface(thumbnail.as_deref());
+        }
+    }
+
+    // Members who still owe part of the fee stand out
+    fn balance_cell_data_func(&self, cell: &gtk::CellRenderer, model: &gtk::TreeModel, iter: &gtk::TreeIter) {
+        let ledger: &Ledger = &*self.ledger.borrow();
+        let number: String = model.value(iter, Column::Number.into()).get().unwrap_or_default();
+        let id: String = model.value(iter, Column::ID.into()).get().unwrap_or_default();
+        let date: String = model.value(iter, Column::Date.into()).get().unwrap_or_default();
+
+        let balance = ledger.balance(&number, &id, &date, self.settings.membership_fee());
+
+        if let Some(cell) = cell.downcast_ref::<gtk::CellRendererText>() {
+            if balance < 0 {
+                cell.set_markup(Some(&format!("<span foreground='red'>{}</span>", ledger::format_amount(balance))));
+            } else {
+                cell.set_text(Some(&ledger::format_amount(balance)));
+            }
+        }
+    }
+
+    fn last_paid_cell_data_func(&self, cell: &gtk::CellRenderer, model: &gtk::TreeModel, iter: &gtk::TreeIter) {
+        let ledger: &Ledger = &*self.ledger.borrow();
+        let number: String = model.value(iter, Column::Number.into()).get().unwrap_or_default();
+        let id: String = model.value(iter, Column::ID.into()).get().unwrap_or_default();
+
+        if let Some(cell) = cell.downcast_ref::<gtk::CellRendererText>() {
+            cell.set_text(ledger.last_paid(&number, &id).map(|payment| payment.date.as_str()));
+        }
+    }
+
+    // Thumbnails are cached, so that we don't have to hit the disk
+    // every time a row is drawn
+    fn signature_thumbnail(&self, number: &str) -> Option<cairo::ImageSurface> {
+        let mut thumbnails = self.thumbnails.borrow_mut();
+
+        thumbnails.entry(String::from(number)).or_insert_with(|| {
+            self.load_signature(number).and_then(|signature| signature.thumbnail(24))
+        }).clone()
+    }
+
+    // Signatures that can't be read are treated as missing
+    fn load_signature(&self, number: &str) -> Option<Signature> {
+        self.storage().load_signature(number).ok().flatten()
+    }
+
+    // Signatures are stored right away rather than when the document
+    // is saved, same as all other data that's not part of the records
+    fn store_signature(&self, number: &str, signature: &Signature) {
+        let result = self.storage().save_signature(number, signature);
+
+        self.thumbnails.borrow_mut().remove(number);
+        self.treeview.queue_draw();
+
+        if let Err(e) = result {
+            self.show_error(&format!("Failed to store signature: {}", e));
+        }
+    }
+
+    fn count_matches(&self, needle: &Needle) -> usize {
+        let data: &ListStore = &*self.data.borrow();
+
+        let mut count = 0;
+        let iter: Option<gtk::TreeIter> = data.iter_first();
+
+        if let Some(iter) = iter {
+            loop {
+                if self.score(&iter, needle).is_some() {
+                    count += 1;
+                }
+
+                if !data.iter_next(&iter) { break; }
+            }
+        }
+
+        count
+    }
+
+    fn update_saved_searches(&self) {
+        for child in self.savedlist.children() {
+            self.savedlist.remove(&child);
+        }
+
+        let fuzzy = self.fuzzysearchaction.state();
+
+        for (index, (name, query)) in self.settings.saved_searches().iter().enumerate() {
+            let count = self.count_matches(&Needle::new(query, fuzzy));
+
+            let name_label = gtk::Label::new(Some(name));
+            name_label.set_xalign(0.0);
+            name_label.set_tooltip_text(Some(query));
+
+            let count_label = gtk::Label::new(Some(&count.to_string()));
+            count_label.style_context().add_class("dim-label");
+
+            let remove_button = gtk::Button::from_icon_name(
+                Some("list-remove-symbolic"),
+                gtk::IconSize::Button,
+            );
+            remove_button.set_relief(gtk::ReliefStyle::None);
+            remove_button.set_tooltip_text(Some("Remove"));
+            let _self = self.clone();
+            remove_button.connect_clicked(move |_| {
+                _self.remove_search_clicked(index);
+            });
+
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+            row.pack_start(&name_label, true, true, 0);
+            row.pack_start(&count_label, false, false, 0);
+            row.pack_start(&remove_button, false, false, 0);
+
+            self.savedlist.add(&row);
+        }
+
+        self.savedlist.show_all();
     }
 
     // Returns true if it's okay to discard changes in the current
@@ -494,27 +1470,270 @@ impl Window {
         ret
     }
 
+    // Returns true if it's okay to save the current document as part
+    // of an action, either because the user has confirmed or because
+    // there are no other changes that would be saved along with it
+    fn save_changes_okay(&self, explanation: &str) -> bool {
+        if !self.is_dirty() {
+            return true;
+        }
+
+        let dialog = gtk::MessageDialog::new(
+            Some(&self.parent),
+            gtk::DialogFlags::MODAL,
+            gtk::MessageType::Question,
+            gtk::ButtonsType::OkCancel,
+            "Save this registry?",
+        );
+        dialog.set_secondary_text(Some(explanation));
+
+        let response = dialog.run();
+
+        unsafe {
+            dialog.destroy();
+        }
+
+        response == gtk::ResponseType::Ok
+    }
+
+    // Returns all values for the record at the path, which must be
+    // a path in the actual model
+    fn record(&self, path: &gtk::TreePath) -> [String; Column::SIZE] {
+        let data: &ListStore = &*self.data.borrow();
+        let iter: gtk::TreeIter = data.iter(path).unwrap();
+
+        data.record(&iter)
+    }
+
+    // The card number the signature of the record at the path, which
+    // must be a path in the actual model, is stored under
+    fn signature_key(&self, path: &gtk::TreePath) -> String {
+        let data: &ListStore = &*self.data.borrow();
+        let iter: gtk::TreeIter = data.iter(path).unwrap();
+
+        data.key(&iter)
+    }
+
+    // Records with the same card number would end up sharing the
+    // same signature
+    fn check_signature_key(&self, path: &gtk::TreePath) -> bool {
+        let key = self.signature_key(path);
+        let count = self.all_paths().iter().filter(|other| self.signature_key(other) == key).count();
+
+        if count > 1 {
+            self.show_error(&format!(
+                "Card N. {} is used by more than one member. Give each member their own card number and save the registry before storing signatures.",
+                key,
+            ));
+            return false;
+        }
+
+        true
+    }
+
+    // Paths in the actual model for all records, in the order they
+    // are stored in
+    fn all_paths(&self) -> Vec<gtk::TreePath> {
+        let data: &ListStore = &*self.data.borrow();
+
+        (0..data.len()).map(|i| gtk::TreePath::from_indicesv(&[i])).collect()
+    }
+
+    // Paths in the actual model for the records currently displayed
+    // in the treeview, in the order they're displayed in
+    fn visible_paths(&self) -> Vec<gtk::TreePath> {
+        let sorted_data: &gtk::TreeModelSort = &*self.sorted_data.borrow();
+        let count = sorted_data.iter_n_children(None);
+
+        (0..count).map(|i| {
+            self.convert_path(gtk::TreePath::from_indicesv(&[i]))
+        }).collect()
+    }
+
+    // Paths in the actual model for the records currently selected
+    fn selected_paths(&self) -> Vec<gtk::TreePath> {
+        let (paths, _) = self.treeview.selection().selected_rows();
+
+        paths.into_iter().map(|path| self.convert_path(path)).collect()
+    }
+
+    fn show_error(&self, message: &str) {
+        let dialog = gtk::MessageDialog::new(
+            Some(&self.parent),
+            gtk::DialogFlags::empty(),
+            gtk::MessageType::Error,
+            gtk::ButtonsType::Close,
+            message,
+        );
+
+        dialog.run();
+
+        unsafe {
+            dialog.destroy();
+        }
+    }
+
     fn convert_path(&self, path: gtk::TreePath) -> gtk::TreePath {
         let filtered_data: &gtk::TreeModelFilter = &*self.filtered_data.borrow();
+        let sorted_data: &gtk::TreeModelSort = &*self.sorted_data.borrow();
 
-        // Since we use filtering on the data displayed in the
-        // treeview, we have to convert paths from the filtered
+        // Since we use sorting and filtering on the data displayed
+        // in the treeview, we have to convert paths from the sorted
         // model to the actual model before using them
+        let path = sorted_data.convert_path_to_child_path(&path).unwrap();
         filtered_data.convert_path_to_child_path(&path).unwrap()
     }
 
+    // The opposite of convert_path(): returns None if the record is
+    // not currently displayed in the treeview
+    fn convert_child_path(&self, path: &gtk::TreePath) -> Option<gtk::TreePath> {
+        let filtered_data: &gtk::TreeModelFilter = &*self.filtered_data.borrow();
+        let sorted_data: &gtk::TreeModelSort = &*self.sorted_data.borrow();
+
+        filtered_data.convert_child_path_to_path(path).and_then(|path| {
+            sorted_data.convert_child_path_to_path(&path)
+        })
+    }
+
     fn update_column(&self, path: gtk::TreePath, column: &Column, text: &str) {
-        let data: &ListStore = &*self.data.borrow();
         let path: gtk::TreePath = self.convert_path(path);
-        let iter: gtk::TreeIter = data.iter(&path).unwrap();
-        let value: Option<String> = data.value(&iter, column);
 
-        if let Some(current) = value {
-            if text != current {
-                data.set_value(&iter, column, &String::from(text));
-                self.set_dirty(true);
+        self.update_value(&path, column, text);
+    }
+
+    // Same as update_column(), but takes a path in the actual model
+    // rather than in the treeview. Useful when updating several
+    // columns in a row, since the row might move around in the
+    // treeview as a result of sorting
+    fn update_value(&self, path: &gtk::TreePath, column: &Column, text: &str) {
+        self.update_values(vec![(path.clone(), column.clone(), String::from(text))]);
+    }
+
+    // Applies several changes, each made of a path in the actual
+    // model, a column and a value, as a single step that can be
+    // undone all at once
+    fn update_values(&self, changes: Vec<(gtk::TreePath, Column, String)>) {
+        let mut step = Vec::new();
+
+        {
+            let data: &ListStore = &*self.data.borrow();
+
+            for (path, column, text) in changes {
+                let iter: gtk::TreeIter = data.iter(&path).unwrap();
+                let value: Option<String> = data.value(&iter, &column);
+
+                if let Some(current) = value {
+                    if text != current {
+                        data.set_value(&iter, &column, &text);
+                        step.push((path, column, current));
+                    }
+                }
+            }
+        }
+
+        if !step.is_empty() {
+            self.undo.borrow_mut().push(step);
+            self.undoaction.set_enabled(true);
+            self.set_dirty(true);
+            self.update_problems();
+        }
+    }
+
+    // The panel is only kept up to date while it's displayed
+    fn update_problems(&self) {
+        if !self.problemsaction.state() {
+            return;
+        }
+
+        let records = self.records();
+        let problems = problems::scan(&records, &self.validators);
+
+        for child in self.problemslist.children() {
+            self.problemslist.remove(&child);
+        }
+
+        for (index, problem) in problems.iter().enumerate() {
+            let record = &records[problem.record];
+
+            let label = gtk::Label::new(Some(&format!(
+                "N. {} · {}: {}",
+                record[usize::from(Column::Number)],
+                problem.column.title(),
+                problem.message,
+            )));
+            label.set_xalign(0.0);
+            label.set_ellipsize(pango::EllipsizeMode::End);
+
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+            row.set_margin_start(6);
+            row.set_margin_end(6);
+            row.pack_start(&label, true, true, 0);
+
+            if let Some(fix) = &problem.fix {
+                let button = match fix {
+                    Fix::Set(value) => {
+                        let button = gtk::Button::with_label("Fix");
+                        button.set_tooltip_text(Some(&format!("Change to “{}”", value)));
+                        button
+                    },
+                    Fix::Remove => gtk::Button::with_label("Remove"),
+                };
+                button.set_relief(gtk::ReliefStyle::None);
+
+                let _self = self.clone();
+                button.connect_clicked(move |_| {
+                    _self.fix_problem_clicked(index);
+                });
+
+                row.pack_start(&button, false, false, 0);
+            }
+
+            self.problemslist.add(&row);
+        }
+        self.problemslist.show_all();
+
+        self.problemslabel.set_markup(&match problems.len() {
+            0 => String::from("<b>No problems found</b>"),
+            1 => String::from("<b>1 problem</b>"),
+            n => format!("<b>{} problems</b>", n),
+        });
+
+        *self.problems.borrow_mut() = problems;
+    }
+
+    // The column in the treeview displaying the column in the model,
+    // if any
+    fn view_column(&self, column: &Column) -> Option<gtk::TreeViewColumn> {
+        self.treeview.columns().into_iter().find(|c| {
+            c.sort_column_id() == i32::from(column.clone())
+        })
+    }
+
+    // Takes paths in the actual model
+    fn remove_records(&self, mut paths: Vec<gtk::TreePath>) {
+        {
+            let data: &ListStore = &*self.data.borrow();
+
+            // Remove rows starting from the last one, so that the
+            // paths of the remaining ones stay valid
+            paths.sort();
+            for path in paths.iter().rev() {
+                if let Some(iter) = data.iter(path) {
+                    data.remove(&iter);
+                }
             }
         }
+
+        self.clear_undo();
+        self.set_dirty(true);
+        self.update_problems();
+    }
+
+    // Undo steps refer to records by their path, so they have to be
+    // forgotten whenever records are removed or replaced
+    fn clear_undo(&self) {
+        self.undo.borrow_mut().clear();
+        self.undoaction.set_enabled(false);
     }
 
     // High-level actions
@@ -528,6 +1747,8 @@ impl Window {
     }
 
     fn stop_search_action(&self) {
+        self.stop_check_in_action();
+        self.scanmodeaction.change_state(false);
         self.searchbar.set_search_mode(false);
         self.togglesearchaction.change_state(false);
         self.insertaction.set_enabled(true);
@@ -536,46 +1757,804 @@ impl Window {
     fn insert_action(&self) {
         let data: &ListStore = &*self.data.borrow();
 
-        let mut number: i32 = 1;
-        let iter: Option<gtk::TreeIter> = data.iter_first();
+        let number = column::next_number(self.all_paths().iter().map(|path| {
+            self.record(path)[usize::from(Column::Number)].clone()
+        })).to_string();
+
+        let today = chrono::Local::today();
+        let date = today.format("%d/%m/%y").to_string();
+
+        // Create an empty record
+        let mut values = ListStore::new_row();
+
+        // Fill in some sensible data: the next number in the
+        // sequence and today's date
+        values[usize::from(Column::Number)] = number;
+        values[usize::from(Column::Date)] = date;
+
+        let iter: gtk::TreeIter = data.append();
+
+        // Insert the fresh data
+        data.set_all_values(&iter, &values);
+
+        self.update_problems();
+
+        // Scroll to it and start editing right away
+        let path: gtk::TreePath = data.path(&iter).unwrap();
+        if let Some(path) = self.convert_child_path(&path) {
+            self.treeview.scroll_to_cell(Some(&path), Some(&self.peoplecolumn), false, 0.0, 0.0);
+            self.treeview.set_cursor(&path, Some(&self.peoplecolumn), true);
+        }
+    }
+
+    fn apply_saved_search_action(&self, index: usize) {
+        self.savedpopover.popdown();
+
+        if let Some((_, query)) = self.settings.saved_searches().get(index) {
+            self.searchentry.set_text(query);
+        }
+    }
+
+    fn save_search_action(&self) {
+        let query = self.searchentry.text();
+
+        self.savedpopover.popdown();
+
+        if query.trim().is_empty() {
+            return
+        }
+
+        let dialog = gtk::Dialog::with_buttons(
+            Some("Save search"),
+            Some(&self.parent),
+            gtk::DialogFlags::MODAL,
+            &[("Cancel", gtk::ResponseType::Cancel), ("Save", gtk::ResponseType::Ok)],
+        );
+        dialog.set_default_response(gtk::ResponseType::Ok);
+
+        let entry = gtk::Entry::new();
+        entry.set_text(&query);
+        entry.set_activates_default(true);
+        entry.set_margin(12);
+        dialog.content_area().add(&entry);
+        dialog.show_all();
+
+        if dialog.run() == gtk::ResponseType::Ok {
+            let name = entry.text();
+
+            if !name.trim().is_empty() {
+                let mut searches = self.settings.saved_searches();
+                searches.push((name.trim().to_string(), query.to_string()));
+                self.settings.set_saved_searches(&searches);
+            }
+        }
+
+        unsafe {
+            dialog.destroy();
+        }
+    }
+
+    fn remove_search_action(&self, index: usize) {
+        let mut searches = self.settings.saved_searches();
+
+        if index < searches.len() {
+            searches.remove(index);
+            self.settings.set_saved_searches(&searches);
+        }
+
+        self.update_saved_searches();
+    }
+
+    fn member_details_action(&self, path: gtk::TreePath) {
+        let path: gtk::TreePath = self.convert_path(path);
+        let values = self.record(&path);
+
+        let key = self.signature_key(&path);
+        let signature = self.load_signature(&key);
+        let dialog = MemberDialog::new(&self.parent, &values, signature);
+
+        if let Some(values) = dialog.run() {
+            self.update_values(values.iter().enumerate().map(|(i, value)| {
+                (path.clone(), Column::from(i), value.clone())
+            }).collect());
+
+            if let Some(signature) = dialog.changed_signature() {
+                if self.check_signature_key(&path) {
+                    self.store_signature(&key, &signature);
+                }
+            }
+        }
+    }
+
+    // Changes a column across several records at once, either those
+    // selected or all those currently displayed
+    fn bulk_edit_action(&self) {
+        let selected = self.selected_paths();
+        let shown = self.visible_paths();
+
+        let dialog = BulkEditDialog::new(&self.parent, selected.len(), shown.len());
+
+        let edit = match dialog.run() {
+            Some(edit) => edit,
+            None => return,
+        };
+
+        let paths = if edit.selected_only { selected } else { shown };
+        let index = usize::from(edit.column.clone());
+
+        let changes = paths.into_iter().map(|path| {
+            let value = edit.change.apply(&self.record(&path)[index]);
+            (path, edit.column.clone(), value)
+        }).collect();
+
+        self.update_values(changes);
+    }
+
+    // Works on all records rather than only those matching the
+    // current search. Each batch of replacements can be undone as
+    // a whole
+    fn find_replace_action(&self) {
+        let dialog = FindReplaceDialog::new(&self.parent, self.records());
+
+        dialog.run(|changes| {
+            self.update_values(changes.into_iter().map(|(i, column, value)| {
+                (gtk::TreePath::from_indicesv(&[i as i32]), column, value)
+            }).collect());
+
+            self.records()
+        });
+    }
+
+    // Groups the user has dismissed are remembered for each document,
+    // so that they're not suggested again
+    fn duplicates_action(&self) {
+        let records = self.records();
+        let uri = self.source_uri.borrow().clone();
+        let mut dismissed = self.settings.dismissed_duplicates(&uri);
+
+        let groups: Vec<duplicates::Group> = duplicates::find(&records).into_iter().filter(|group| {
+            !dismissed.contains(&group.key(&records))
+        }).collect();
+
+        if groups.is_empty() {
+            let dialog = gtk::MessageDialog::new(
+                Some(&self.parent),
+                gtk::DialogFlags::empty(),
+                gtk::MessageType::Info,
+                gtk::ButtonsType::Close,
+                "No possible duplicates found.",
+            );
+
+            dialog.run();
+
+            unsafe {
+                dialog.destroy();
+            }
+
+            return;
+        }
+
+        let dialog = DuplicatesDialog::new(&self.parent, &records, &groups);
+
+        let decisions = match dialog.run() {
+            Some(decisions) => decisions,
+            None => return,
+        };
+
+        let mut changes = Vec::new();
+        let mut removed = Vec::new();
+        let mut renumbered = Vec::new();
+        let mut signatures = Vec::new();
+
+        for (group, decision) in groups.iter().zip(decisions) {
+            match decision {
+                Decision::Undecided => {},
+                Decision::Dismiss => dismissed.push(group.key(&records)),
+                Decision::Merge => {
+                    let keep = group.records[0];
+                    let path = gtk::TreePath::from_indicesv(&[keep as i32]);
+
+                    let merged = duplicates::merge(&group.records.iter().map(|&i| &records[i]).collect::<Vec<_>>());
+                    let number = &merged[usize::from(Column::Number)];
+                    for (i, value) in merged.iter().enumerate() {
+                        changes.push((path.clone(), Column::from(i), value.clone()));
+                    }
+
+                    // Payments and signatures belonging to the records
+                    // that go away are moved over to the one that stays.
+                    // Signatures are read now, since the records they're
+                    // stored for won't be around after saving
+                    for &other in &group.records[1..] {
+                        let other_path = gtk::TreePath::from_indicesv(&[other as i32]);
+                        let other_number = &records[other][usize::from(Column::Number)];
+
+                        renumbered.push((other_number.clone(), number.clone()));
+
+                        if let Some(signature) = self.load_signature(&self.signature_key(&other_path)) {
+                            signatures.push((number.clone(), signature));
+                        }
+
+                        removed.push(other_path);
+                    }
+                },
+            }
+        }
+
+        self.settings.set_dismissed_duplicates(&uri, &dismissed);
+
+        if removed.is_empty() {
+            return;
+        }
+
+        // Payments and signatures are stored right away, so the merge
+        // is saved as well, otherwise they wouldn't match the records
+        if !self.save_changes_okay("Merging members saves this registry, including the changes that haven't been saved yet.") {
+            return;
+        }
+
+        self.update_values(changes);
+        self.remove_records(removed);
+        self.write_data();
+
+        {
+            let mut ledger = self.ledger.borrow_mut();
+            for (from, to) in &renumbered {
+                ledger.renumber(from, to);
+            }
+        }
+
+        let result = self.storage().save_payments(self.ledger.borrow().payments());
+        if let Err(e) = result {
+            self.show_error(&format!("Failed to store payments: {}", e));
+        }
+
+        // The record that stays keeps its own signature, if it has one
+        for (number, signature) in signatures {
+            if self.load_signature(&number).is_none() {
+                self.store_signature(&number, &signature);
+            }
+        }
+    }
+
+    // Reverts the most recent change to existing records. Records
+    // added in the meantime are left alone
+    fn undo_action(&self) {
+        let step = self.undo.borrow_mut().pop();
+
+        if let Some(step) = step {
+            {
+                let data: &ListStore = &*self.data.borrow();
+
+                for (path, column, value) in step.iter().rev() {
+                    if let Some(iter) = data.iter(path) {
+                        data.set_value(&iter, column, value);
+                    }
+                }
+            }
+
+            self.undoaction.set_enabled(!self.undo.borrow().is_empty());
+            self.set_dirty(true);
+            self.update_problems();
+        }
+    }
+
+    fn capture_signature_action(&self) {
+        let (path, _) = self.treeview.cursor();
+        let path: gtk::TreePath = match path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let path: gtk::TreePath = self.convert_path(path);
+
+        if !self.check_signature_key(&path) {
+            return;
+        }
+
+        let dialog = SignatureDialog::new(&self.parent);
+
+        if let Some(signature) = dialog.run() {
+            self.store_signature(&self.signature_key(&path), &signature);
+        }
+    }
+
+    fn edit_people_action(&self) {
+        let (path, _) = self.treeview.cursor();
+        let path: gtk::TreePath = match path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let value: String = {
+            let data: &ListStore = &*self.data.borrow();
+            let iter: gtk::TreeIter = data.iter(&self.convert_path(path.clone())).unwrap();
+
+            data.value(&iter, &Column::People).unwrap_or_default()
+        };
+
+        let dialog = PeopleDialog::new(&self.parent, &Person::parse_list(&value));
+
+        if let Some(people) = dialog.run() {
+            self.update_column(path, &Column::People, &Person::format_list(&people));
+        }
+    }
+
+    fn payments_action(&self) {
+        let (path, _) = self.treeview.cursor();
+        let path: gtk::TreePath = match path {
+            Some(path) => self.convert_path(path),
+            None => return,
+        };
+
+        let record = self.record(&path);
+        let number = &record[usize::from(Column::Number)];
+        let id = &record[usize::from(Column::ID)];
+
+        if number.is_empty() {
+            self.show_error("Payments can only be recorded for members with a card number.");
+            return;
+        }
+
+        let payments = self.ledger.borrow().payments_for(number, id);
+        let dialog = PaymentsDialog::new(
+            &self.parent,
+            number,
+            id,
+            &record[usize::from(Column::People)],
+            &payments,
+            self.settings.membership_fee(),
+        );
+
+        if let Some(payments) = dialog.run() {
+            let (result, payment) = {
+                let mut ledger = self.ledger.borrow_mut();
+                ledger.set_payments_for(number, id, payments);
+
+                let unnumbered = ledger.payments().to_vec();
+                let payment = dialog.receipt_requested().and_then(|index| {
+                    ledger.issue_receipt(number, id, index)
+                });
+
+                let result = self.storage().save_payments(ledger.payments());
+
+                // A receipt number that hasn't been stored would be
+                // handed out again, so it's taken back
+                if result.is_err() {
+                    *ledger = Ledger::new(unnumbered);
+                }
+
+                (result, payment)
+            };
+
+            self.treeview.queue_draw();
+
+            if let Err(e) = result {
+                self.show_error(&format!("Failed to store payments: {}", e));
+                return;
+            }
+
+            if let Some(payment) = payment {
+                self.show_receipt(&record, &payment);
+            }
+        }
+    }
+
+    // Receipts are only generated once, and simply displayed again
+    // when reprinting them, so that they can't change after having
+    // been issued
+    fn show_receipt(&self, record: &[String], payment: &Payment) {
+        let path = receipt::path_for(&*self.source_filename.borrow(), &payment.receipt);
+
+        if !path.exists() {
+            if let Some(parent) = path.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+
+            let receipt = Receipt::new(&self.settings, record, payment);
+
+            if let Err(e) = receipt.export_pdf(&path) {
+                self.show_error(&format!("Failed to create receipt: {}", e));
+                return;
+            }
+        }
+
+        let uri = match glib::filename_to_uri(&path, None) {
+            Ok(uri) => uri,
+            Err(e) => {
+                self.show_error(&format!("Failed to open receipt: {}", e));
+                return;
+            },
+        };
+
+        if let Err(e) = gtk::show_uri_on_window(Some(&self.parent), &uri, gtk::current_event_time()) {
+            self.show_error(&format!("Failed to open receipt: {}", e));
+        }
+    }
+
+    // Goes back to ranking records by how well they match the search,
+    // which is what's used until a column header is clicked
+    fn sort_by_relevance_action(&self) {
+        let sorted_data: &gtk::TreeModelSort = &*self.sorted_data.borrow();
+
+        sorted_data.set_sort_column_id(gtk::SortColumn::Default, gtk::SortType::Ascending);
+    }
+
+    fn cash_summary_action(&self) {
+        let payments = self.ledger.borrow().payments().to_vec();
+        let dialog = CashSummaryDialog::new(&self.parent, payments);
+
+        dialog.run();
+    }
+
+    fn stats_action(&self) {
+        let searching = !self.filter_needle.borrow().is_empty();
+
+        let all = self.all_paths().iter().map(|path| self.record(path)).collect();
+        let matching = if searching {
+            Some(self.visible_paths().iter().map(|path| self.record(path)).collect())
+        } else {
+            None
+        };
+
+        let dialog = StatsDialog::new(&self.parent, all, matching);
+
+        dialog.run();
+    }
+
+    // Cards are produced for the selected records or, if none is
+    // selected, for all records currently displayed. Returns None if
+    // there are no such records
+    fn card_printer(&self) -> Option<CardPrinter> {
+        let mut paths = self.selected_paths();
+        if paths.is_empty() {
+            paths = self.visible_paths();
+        }
+
+        if paths.is_empty() {
+            self.show_error("There are no members to produce cards for.");
+            return None;
+        }
+
+        let records = paths.iter().map(|path| self.record(path)).collect();
+
+        Some(CardPrinter::new(CardTemplate::load(), records))
+    }
+
+    fn print_cards_action(&self) {
+        let printer = match self.card_printer() {
+            Some(printer) => printer,
+            None => return,
+        };
+
+        if let Err(e) = printer.print(&self.parent) {
+            self.show_error(&format!("Failed to print cards: {}", e));
+        }
+    }
+
+    fn export_cards_action(&self) {
+        let printer = match self.card_printer() {
+            Some(printer) => printer,
+            None => return,
+        };
+
+        let dialog = gtk::FileChooserDialog::new(
+            Some("Export cards"),
+            Some(&self.parent),
+            gtk::FileChooserAction::Save,
+        );
+        dialog.add_button("Cancel", gtk::ResponseType::Cancel);
+        dialog.add_button("Export", gtk::ResponseType::Ok);
+        dialog.set_do_overwrite_confirmation(true);
+        dialog.set_current_name("cards.pdf");
+
+        let filename = match dialog.run() {
+            gtk::ResponseType::Ok => dialog.filename(),
+            _ => None,
+        };
+
+        unsafe {
+            dialog.destroy();
+        }
+
+        if let Some(filename) = filename {
+            if let Err(e) = printer.export_pdf(&filename) {
+                self.show_error(&format!("Failed to export cards: {}", e));
+            }
+        }
+    }
+
+    // While searching, the user gets to choose whether the report
+    // should include the matching records only or all of them. In
+    // both cases, the current sort order is respected. Returns None
+    // if the user has cancelled the operation
+    fn report(&self) -> Option<Report> {
+        let searching = !self.filter_needle.borrow().is_empty();
+
+        let all = if !searching {
+            true
+        } else {
+            let dialog = gtk::MessageDialog::new(
+                Some(&self.parent),
+                gtk::DialogFlags::MODAL,
+                gtk::MessageType::Question,
+                gtk::ButtonsType::None,
+                "Include all members in the registry?",
+            );
+            dialog.set_secondary_text(Some("A search is currently active, so only some of the members are displayed."));
+            dialog.add_button("Cancel", gtk::ResponseType::Cancel);
+            dialog.add_button("Matching only", gtk::ResponseType::No);
+            dialog.add_button("All members", gtk::ResponseType::Yes);
+
+            let response = dialog.run();
+
+            unsafe {
+                dialog.destroy();
+            }
+
+            match response {
+                gtk::ResponseType::Yes => true,
+                gtk::ResponseType::No => false,
+                _ => return None,
+            }
+        };
+
+        let records = if all {
+            let mut records: Vec<[String; Column::SIZE]> = self.all_paths().iter().map(|path| {
+                self.record(path)
+            }).collect();
+
+            // Records that are not displayed can't be looked up in
+            // the sorted model, so they have to be sorted by hand
+            let sorted_data: &gtk::TreeModelSort = &*self.sorted_data.borrow();
+            if let Some((gtk::SortColumn::Index(column), order)) = sorted_data.sort_column_id() {
+                Column::from(column).sort(&mut records, order == gtk::SortType::Descending);
+            }
+
+            records
+        } else {
+            self.visible_paths().iter().map(|path| self.record(path)).collect()
+        };
+
+        Some(Report::new(&self.settings.association_name(), records))
+    }
+
+    fn print_report_action(&self) {
+        if let Some(report) = self.report() {
+            if let Err(e) = report.print(&self.parent) {
+                self.show_error(&format!("Failed to print registry: {}", e));
+            }
+        }
+    }
+
+    fn export_report_action(&self) {
+        let report = match self.report() {
+            Some(report) => report,
+            None => return,
+        };
+
+        let dialog = gtk::FileChooserDialog::new(
+            Some("Export registry"),
+            Some(&self.parent),
+            gtk::FileChooserAction::Save,
+        );
+        dialog.add_button("Cancel", gtk::ResponseType::Cancel);
+        dialog.add_button("Export", gtk::ResponseType::Ok);
+        dialog.set_do_overwrite_confirmation(true);
+        dialog.set_current_name("registry.pdf");
+
+        let filename = match dialog.run() {
+            gtk::ResponseType::Ok => dialog.filename(),
+            _ => None,
+        };
+
+        unsafe {
+            dialog.destroy();
+        }
+
+        if let Some(filename) = filename {
+            if let Err(e) = report.export_pdf(&filename) {
+                self.show_error(&format!("Failed to export registry: {}", e));
+            }
+        }
+    }
+
+    // Asks for the name of the event members are checking in at,
+    // suggesting the current date. Returns None if the user has
+    // cancelled the operation
+    fn ask_event_name(&self) -> Option<String> {
+        let dialog = gtk::Dialog::with_buttons(
+            Some("Check-in"),
+            Some(&self.parent),
+            gtk::DialogFlags::MODAL,
+            &[("Cancel", gtk::ResponseType::Cancel), ("Start", gtk::ResponseType::Ok)],
+        );
+        dialog.set_default_response(gtk::ResponseType::Ok);
+
+        let entry = gtk::Entry::new();
+        entry.set_text(&chrono::Local::today().format("%Y-%m-%d").to_string());
+        entry.set_activates_default(true);
+
+        let label = gtk::Label::new(Some("Event"));
+        label.style_context().add_class("dim-label");
+
+        let hbox = gtk::Box::new(gtk::Orientation::Horizontal, 12);
+        hbox.set_border_width(12);
+        hbox.pack_start(&label, false, false, 0);
+        hbox.pack_start(&entry, true, true, 0);
+        dialog.content_area().add(&hbox);
+        dialog.show_all();
+
+        let ret = match dialog.run() {
+            gtk::ResponseType::Ok => Some(entry.text().trim().to_string()),
+            _ => None,
+        };
+
+        unsafe {
+            dialog.destroy();
+        }
+
+        ret.filter(|event| !event.is_empty())
+    }
+
+    fn start_check_in_action(&self) {
+        let event = match self.ask_event_name() {
+            Some(event) => event,
+            None => return,
+        };
+
+        let entries = match self.storage().load_attendance(&event) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.show_error(&format!("Failed to open attendance: {}", e));
+                return;
+            },
+        };
+        *self.attendance.borrow_mut() = Some(Attendance::new(&event, entries));
+
+        self.checkinaction.change_state(true);
+        self.exportattendanceaction.set_enabled(true);
+
+        self.start_search_action();
+        self.searchentry.set_text("");
+
+        self.show_check_in_result(gtk::MessageType::Info, "Scan or type a card number");
+        self.checkinbar.show();
+    }
+
+    fn stop_check_in_action(&self) {
+        *self.attendance.borrow_mut() = None;
+
+        self.checkinaction.change_state(false);
+        self.exportattendanceaction.set_enabled(false);
+        self.checkinbar.hide();
+    }
 
-        if let Some(iter) = iter {
-            loop {
-                let value: Option<String> = data.value(&iter, &Column::Number);
+    // Cards are valid for the year they've been issued in. Cards
+    // without an issue date are given the benefit of the doubt
+    fn is_expired(date: &str) -> bool {
+        column::parse_date(date).map_or(false, |date| {
+            date.year() < chrono::Local::today().year()
+        })
+    }
 
-                if let Some(value) = value {
-                    number = match value.parse::<i32>() {
-                        Ok(value) => cmp::max(number, value + 1),
-                        Err(_) => number,
-                    }
-                }
+    // Marks the member whose card number has been entered in the
+    // search bar as present, warning the user if that's not possible
+    fn check_in(&self) {
+        let number = self.filter_needle.borrow().as_number().map(String::from);
+        let path = self.visible_paths().into_iter().next();
+
+        let (number, path) = match (number, path) {
+            (Some(number), Some(path)) => (number, path),
+            (Some(number), None) => {
+                self.show_check_in_result(gtk::MessageType::Error, &format!("Unknown card N. {}", number));
+                return;
+            },
+            _ => {
+                self.show_check_in_result(gtk::MessageType::Error, "Not a card number");
+                return;
+            },
+        };
 
-                if !data.iter_next(&iter) { break; }
+        let record = self.record(&path);
+        let people = &record[usize::from(Column::People)];
+
+        let result = {
+            let mut attendance = self.attendance.borrow_mut();
+            let attendance = match attendance.as_mut() {
+                Some(attendance) => attendance,
+                None => return,
+            };
+
+            if let Some(time) = attendance.checked_in(&number) {
+                Ok((gtk::MessageType::Warning, format!("{} already checked in at {}", people, time)))
+            } else if Window::is_expired(&record[usize::from(Column::Date)]) {
+                Ok((gtk::MessageType::Warning, format!("Card N. {} has expired: {}", number, people)))
+            } else {
+                // The list is stored right away, so that nothing is
+                // lost if the application is closed without saving
+                // the document
+                let time = chrono::Local::now().format("%H:%M:%S").to_string();
+
+                self.storage().add_attendance(attendance.event(), &number, &time).map(|_| {
+                    attendance.check_in(&number, &time);
+                    (gtk::MessageType::Info, format!("Welcome, {}", people))
+                })
             }
+        };
+
+        match result {
+            Ok((message_type, message)) => self.show_check_in_result(message_type, &message),
+            Err(e) => self.show_error(&format!("Failed to store attendance: {}", e)),
         }
+    }
 
-        let number: String = fmt::format(format_args!("{}", number));
+    // Results are displayed in large type, so that they can be
+    // read at a glance while people are queueing
+    fn show_check_in_result(&self, message_type: gtk::MessageType, message: &str) {
+        let attendance = self.attendance.borrow();
+
+        self.checkinbar.set_message_type(message_type);
+        self.checkinlabel.set_markup(&format!(
+            "<span size='xx-large' weight='bold'>{}</span>",
+            glib::markup_escape_text(message),
+        ));
+
+        if let Some(attendance) = &*attendance {
+            self.attendancelabel.set_markup(&format!(
+                "{}\n<span size='x-large' weight='bold'>{} present</span>",
+                glib::markup_escape_text(attendance.event()),
+                attendance.len(),
+            ));
+        }
+    }
 
-        let today = chrono::Local::today();
-        let date = today.format("%d/%m/%y").to_string();
+    fn export_attendance_action(&self) {
+        let event = match &*self.attendance.borrow() {
+            Some(attendance) => String::from(attendance.event()),
+            None => return,
+        };
 
-        // Create an empty record
-        let mut values = ListStore::new_row();
+        let dialog = gtk::FileChooserDialog::new(
+            Some("Export attendance"),
+            Some(&self.parent),
+            gtk::FileChooserAction::Save,
+        );
+        dialog.add_button("Cancel", gtk::ResponseType::Cancel);
+        dialog.add_button("Export", gtk::ResponseType::Ok);
+        dialog.set_do_overwrite_confirmation(true);
+        dialog.set_current_name(&format!("{}.csv", event));
 
-        // Fill in some sensible data: the next number in the
-        // sequence and today's date
-        values[usize::from(Column::Number)] = number;
-        values[usize::from(Column::Date)] = date;
+        let filename = match dialog.run() {
+            gtk::ResponseType::Ok => dialog.filename(),
+            _ => None,
+        };
 
-        let iter: gtk::TreeIter = data.append();
-        let path: gtk::TreePath = data.path(&iter).unwrap();
+        unsafe {
+            dialog.destroy();
+        }
 
-        // Insert the fresh data
-        data.set_all_values(&iter, &values);
+        let filename = match filename {
+            Some(filename) => filename,
+            None => return,
+        };
 
-        // Scroll to it and start editing right away
-        self.treeview.scroll_to_cell(Some(&path), Some(&self.peoplecolumn), false, 0.0, 0.0);
-        self.treeview.set_cursor(&path, Some(&self.peoplecolumn), true);
+        let records: Vec<[String; Column::SIZE]> = self.all_paths().iter().map(|path| {
+            self.record(path)
+        }).collect();
+        let people = |number: &str| {
+            records.iter().find(|record| {
+                record[usize::from(Column::Number)] == number
+            }).map_or(String::new(), |record| record[usize::from(Column::People)].clone())
+        };
+
+        let result = match &*self.attendance.borrow() {
+            Some(attendance) => attendance.export(&filename, people),
+            None => return,
+        };
+
+        if let Err(e) = result {
+            self.show_error(&format!("Failed to export attendance: {}", e));
+        }
     }
 
     fn start_menu_action(&self) {
@@ -588,12 +2567,9 @@ impl Window {
         self.togglemenuaction.change_state(false);
     }
 
+    // The document is opened in a new window, unless this one is
+    // still empty
     fn open_action(&self) {
-        // Don't overwrite changes unless the user is okay with that
-        if !self.discard_changes_okay() {
-            return
-        }
-
         let dialog = gtk::FileChooserDialog::new(
             Some("Choose a file"),
             Some(&self.parent),
@@ -607,8 +2583,7 @@ impl Window {
             let uri = dialog.uri();
 
             if let (Some(filename), Some(uri)) = (filename, uri) {
-                self.set_data_source(filename, uri.to_string());
-                self.load_data();
+                self.application.open_file(filename, uri.to_string(), Some(self));
             }
         }
 
@@ -621,6 +2596,325 @@ impl Window {
         self.save_data();
     }
 
+    // Stores the current records, including unsaved changes, along
+    // with everything related to them in a new SQLite database which
+    // then replaces the current document. The original file is left
+    // untouched
+    fn convert_to_sqlite_action(&self) {
+        let dialog = gtk::FileChooserDialog::new(
+            Some("Convert to SQLite"),
+            Some(&self.parent),
+            gtk::FileChooserAction::Save,
+        );
+        dialog.add_button("Cancel", gtk::ResponseType::Cancel);
+        dialog.add_button("Convert", gtk::ResponseType::Ok);
+        dialog.set_do_overwrite_confirmation(true);
+
+        // Keeping the same name means files stored next to the
+        // document, such as receipts, will still be found
+        let current = self.source_filename.borrow().with_extension("sqlite");
+        if let Some(name) = current.file_name().and_then(|n| n.to_str()) {
+            dialog.set_current_name(name);
+        }
+        if let Some(dir) = current.parent() {
+            dialog.set_current_folder(dir);
+        }
+
+        let target = match dialog.run() {
+            gtk::ResponseType::Ok => dialog.filename().zip(dialog.uri()),
+            _ => None,
+        };
+
+        unsafe {
+            dialog.destroy();
+        }
+
+        let (filename, uri) = match target {
+            Some(target) => target,
+            None => return,
+        };
+
+        if filename == self.source_filename() {
+            self.show_error("Choose a different file to convert the registry to.");
+            return;
+        }
+
+        if !self.check_incomplete_records() {
+            return;
+        }
+
+        // The database is built from scratch in a temporary file, and
+        // only replaces the chosen one once it's complete
+        let mut temporary = filename.clone().into_os_string();
+        temporary.push(".tmp");
+        let temporary = PathBuf::from(temporary);
+
+        // Leftovers from a previous attempt would otherwise end up
+        // mixed with the new data
+        if temporary.exists() {
+            if let Err(e) = fs::remove_file(&temporary) {
+                self.show_error(&format!("Failed to convert registry: {}", e));
+                return;
+            }
+        }
+
+        let keys: Vec<String> = self.all_paths().iter().map(|path| self.signature_key(path)).collect();
+        let result = storage::migrate(&**self.storage(), &SqliteStorage::new(&temporary), &self.records(), &keys).and_then(|_| {
+            Ok(fs::rename(&temporary, &filename)?)
+        });
+
+        if let Err(e) = result {
+            fs::remove_file(&temporary).ok();
+            self.show_error(&format!("Failed to convert registry: {}", e));
+            return;
+        }
+
+        self.open(filename, uri.to_string());
+    }
+
+    // Members moving to a different section are appended to the
+    // corresponding registry with a fresh card number, and either
+    // kept in this one as well or removed from it. Both registries
+    // are saved right away, so that they always match their history
+    fn transfer_action(&self) {
+        let paths = self.selected_paths();
+
+        if paths.is_empty() {
+            self.show_error("Select the members to move or copy first.");
+            return;
+        }
+
+        let dialog = gtk::FileChooserDialog::new(
+            Some("Move or copy to registry"),
+            Some(&self.parent),
+            gtk::FileChooserAction::Open,
+        );
+        dialog.add_button("Cancel", gtk::ResponseType::Cancel);
+        dialog.add_button("Copy", gtk::ResponseType::Apply);
+        dialog.add_button("Move", gtk::ResponseType::Ok);
+        if let Some(dir) = self.source_filename.borrow().parent() {
+            dialog.set_current_folder(dir);
+        }
+
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some("Registries"));
+        filter.add_pattern("*.csv");
+        filter.add_pattern("*.sqlite");
+        filter.add_pattern("*.sqlite3");
+        filter.add_pattern("*.db");
+        dialog.add_filter(&filter);
+
+        let response = dialog.run();
+        let target = match response {
+            gtk::ResponseType::Ok | gtk::ResponseType::Apply => dialog.filename(),
+            _ => None,
+        };
+
+        unsafe {
+            dialog.destroy();
+        }
+
+        let target = match target {
+            Some(target) => target,
+            None => return,
+        };
+        let moving = response == gtk::ResponseType::Ok;
+        let source = self.source_filename();
+
+        if target == source {
+            self.show_error("The members are already in this registry.");
+            return;
+        }
+
+        // If the target registry is open, the new records are added
+        // to it and saved along with everything else, which is only
+        // okay if there's nothing else the user hasn't saved yet
+        let window = self.application.find_window(&target);
+        if let Some(window) = &window {
+            if window.is_dirty() {
+                self.show_error(&format!(
+                    "{} has unsaved changes. Save or discard them before moving or copying members to it.",
+                    target.file_name().map_or(String::new(), |name| name.to_string_lossy().to_string()),
+                ));
+                return;
+            }
+        }
+
+        // Removing the members from this registry has to be saved
+        // too, and with it any other change the user has made
+        let paths = if moving {
+            if !self.save_changes_okay("Moving members saves this registry, including the changes that haven't been saved yet.") {
+                return;
+            }
+
+            // Incomplete records might be removed at this point, so
+            // the selection has to be looked up again
+            if !self.check_incomplete_records() {
+                return;
+            }
+
+            let paths = self.selected_paths();
+            if paths.is_empty() {
+                return;
+            }
+            paths
+        } else {
+            paths
+        };
+
+        let target_storage = storage::open(&target);
+        let existing = match &window {
+            Some(window) => window.records(),
+            None => match target_storage.load() {
+                Ok(records) => records,
+                Err(e) => {
+                    self.show_error(&format!("Failed to open registry: {}", e));
+                    return;
+                },
+            },
+        };
+
+        let number = column::next_number(existing.iter().map(|record| {
+            &record[usize::from(Column::Number)]
+        }));
+
+        let originals: Vec<[String; Column::SIZE]> = paths.iter().map(|path| self.record(path)).collect();
+        let records: Vec<[String; Column::SIZE]> = originals.iter().enumerate().map(|(i, original)| {
+            let mut record = original.clone();
+            record[usize::from(Column::Number)] = (number + i as i32).to_string();
+            record
+        }).collect();
+
+        let result = match &window {
+            Some(window) => {
+                window.receive_records(&records);
+                Ok(())
+            },
+            None => {
+                let mut all = existing;
+                all.extend(records.iter().cloned());
+                target_storage.save(&all)
+            },
+        };
+
+        if let Err(e) = result {
+            self.show_error(&format!("Failed to write registry: {}", e));
+            return;
+        }
+
+        let source_name = self.document_name();
+        let target_name = target.file_name().map_or(String::new(), |name| {
+            name.to_string_lossy().to_string()
+        });
+        let verb = if moving { "Moved" } else { "Copied" };
+
+        for ((path, original), record) in paths.iter().zip(originals.iter()).zip(records.iter()) {
+            let old = &original[usize::from(Column::Number)];
+            let new = &record[usize::from(Column::Number)];
+            let people = &record[usize::from(Column::People)];
+
+            let mut result = Ok(());
+
+            if let Some(signature) = self.load_signature(&self.signature_key(path)) {
+                result = target_storage.save_signature(new, &signature);
+            }
+
+            result = result.and_then(|_| self.storage().append_history(&Entry::new(&format!(
+                "{} N. {} ({}) to {} as N. {}",
+                verb, old, people, target_name, new,
+            )))).and_then(|_| target_storage.append_history(&Entry::new(&format!(
+                "Received N. {} ({}) from {}, where it was N. {}",
+                new, people, source_name, old,
+            ))));
+
+            if let Err(e) = result {
+                self.show_error(&format!("Failed to record transfer: {}", e));
+                break;
+            }
+        }
+
+        if moving {
+            self.remove_records(paths);
+            self.write_data();
+        }
+    }
+
+    // Rows are offered both as tab separated values, which is what
+    // spreadsheets expect, and as CSV
+    fn copy_rows_action(&self) {
+        let records: Vec<[String; Column::SIZE]> = self.selected_paths().iter().map(|path| {
+            self.record(path)
+        }).collect();
+
+        if records.is_empty() {
+            return;
+        }
+
+        let header = self.copyheaderaction.state();
+        let tsv = clipboard::format(&records, b'\t', header);
+        let csv = clipboard::format(&records, b',', header);
+
+        let targets = [
+            gtk::TargetEntry::new("text/tab-separated-values", gtk::TargetFlags::empty(), 0),
+            gtk::TargetEntry::new("text/csv", gtk::TargetFlags::empty(), 1),
+            gtk::TargetEntry::new("UTF8_STRING", gtk::TargetFlags::empty(), 0),
+            gtk::TargetEntry::new("text/plain;charset=utf-8", gtk::TargetFlags::empty(), 0),
+            gtk::TargetEntry::new("text/plain", gtk::TargetFlags::empty(), 0),
+        ];
+
+        let selection = gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD);
+        selection.set_with_data(&targets, move |_, data, info| {
+            let text = if info == 1 { &csv } else { &tsv };
+            data.set(&data.target(), 8, text.as_bytes());
+        });
+    }
+
+    // Pasted records are appended and numbered the same way inserted
+    // ones are, regardless of the numbers they had before. They're
+    // selected afterwards, so that it's easy to see what was added
+    fn paste_rows_action(&self) {
+        let selection = gtk::Clipboard::get(&gdk::SELECTION_CLIPBOARD);
+
+        let mut records = match selection.wait_for_text() {
+            Some(text) => clipboard::parse(&text),
+            None => return,
+        };
+
+        if records.is_empty() {
+            return;
+        }
+
+        let number = column::next_number(self.records().iter().map(|record| {
+            record[usize::from(Column::Number)].clone()
+        }));
+        let date = chrono::Local::today().format("%d/%m/%y").to_string();
+
+        for (i, record) in records.iter_mut().enumerate() {
+            record[usize::from(Column::Number)] = (number + i as i32).to_string();
+
+            if record[usize::from(Column::Date)].is_empty() {
+                record[usize::from(Column::Date)] = date.clone();
+            }
+        }
+
+        let first = self.data.borrow().len();
+        self.append_records(&records);
+
+        let tree_selection = self.treeview.selection();
+        tree_selection.unselect_all();
+
+        for i in 0..records.len() as i32 {
+            let path = gtk::TreePath::from_indicesv(&[first + i]);
+
+            if let Some(path) = self.convert_child_path(&path) {
+                if i == 0 {
+                    self.treeview.scroll_to_cell(Some(&path), None::<&gtk::TreeViewColumn>, false, 0.0, 0.0);
+                }
+                tree_selection.select_path(&path);
+            }
+        }
+    }
+
     fn close_action(&self) -> glib::signal::Inhibit {
         // false means we want to close the window, true means
         // we don't, so we have to flip the result here
@@ -647,14 +2941,176 @@ impl Window {
         self.search();
     }
 
+    fn search_activated(&self) {
+        self.select_match(0);
+
+        if self.checkinaction.state() {
+            self.check_in();
+        }
+
+        // Scanners type the code followed by Enter: selecting the
+        // text means the next code will replace it, so that cards
+        // can be scanned one after the other
+        if self.scanmodeaction.state() || self.checkinaction.state() {
+            self.searchentry.select_region(0, -1);
+        }
+    }
+
+    fn search_key_pressed(&self, event: &gdk::EventKey) -> glib::signal::Inhibit {
+        let keyval = event.keyval();
+
+        // Up and Down cycle through matches without moving the
+        // focus away from the search entry
+        let step = if keyval == gdk::keys::constants::Down {
+            1
+        } else if keyval == gdk::keys::constants::Up {
+            -1
+        } else {
+            return glib::signal::Inhibit(false);
+        };
+
+        let n = match self.selected_match() {
+            Some(current) => current + step,
+            None if step > 0 => 0,
+            None => -1,
+        };
+
+        glib::signal::Inhibit(self.select_match(n))
+    }
+
     fn stop_search(&self) {
         self.stop_search_action();
     }
 
+    fn saved_search_activated(&self, index: i32) {
+        self.apply_saved_search_action(index as usize);
+    }
+
+    fn save_search_clicked(&self) {
+        self.save_search_action();
+    }
+
+    fn remove_search_clicked(&self, index: usize) {
+        self.remove_search_action(index);
+    }
+
     fn insert_action_activated(&self) {
         self.insert_action();
     }
 
+    fn row_activated(&self, path: gtk::TreePath) {
+        self.member_details_action(path);
+    }
+
+    fn edit_people_action_activated(&self) {
+        self.edit_people_action();
+    }
+
+    fn bulk_edit_action_activated(&self) {
+        self.bulk_edit_action();
+    }
+
+    fn find_replace_action_activated(&self) {
+        self.find_replace_action();
+    }
+
+    fn problems_action_activated(&self) {
+        let state = !self.problemsaction.state();
+
+        self.problemsaction.change_state(state);
+        self.problemsrevealer.set_reveal_child(state);
+        self.update_problems();
+    }
+
+    // Records hidden by the current search are displayed by stopping
+    // the search first
+    fn problem_activated(&self, index: i32) {
+        let (record, column) = match self.problems.borrow().get(index as usize) {
+            Some(problem) => (problem.record, problem.column.clone()),
+            None => return,
+        };
+
+        let path = gtk::TreePath::from_indicesv(&[record as i32]);
+
+        if self.convert_child_path(&path).is_none() {
+            self.stop_search_action();
+            self.searchentry.set_text("");
+            self.search();
+        }
+
+        if let Some(path) = self.convert_child_path(&path) {
+            let column = self.view_column(&column);
+
+            self.treeview.scroll_to_cell(Some(&path), column.as_ref(), false, 0.0, 0.0);
+            self.treeview.set_cursor(&path, column.as_ref(), false);
+            self.treeview.grab_focus();
+        }
+    }
+
+    fn fix_problem_clicked(&self, index: usize) {
+        let fix = match self.problems.borrow().get(index) {
+            Some(Problem { record, column, fix: Some(Fix::Set(value)), .. }) => {
+                Some((*record, column.clone(), Some(value.clone())))
+            },
+            Some(Problem { record, column, fix: Some(Fix::Remove), .. }) => {
+                Some((*record, column.clone(), None))
+            },
+            _ => None,
+        };
+
+        let path = |record: usize| gtk::TreePath::from_indicesv(&[record as i32]);
+
+        match fix {
+            Some((record, column, Some(value))) => self.update_value(&path(record), &column, &value),
+            Some((record, _, None)) => self.remove_records(vec![path(record)]),
+            None => {},
+        }
+    }
+
+    fn duplicates_action_activated(&self) {
+        self.duplicates_action();
+    }
+
+    fn undo_action_activated(&self) {
+        self.undo_action();
+    }
+
+    fn capture_signature_action_activated(&self) {
+        self.capture_signature_action();
+    }
+
+    fn print_cards_action_activated(&self) {
+        self.print_cards_action();
+    }
+
+    fn export_cards_action_activated(&self) {
+        self.export_cards_action();
+    }
+
+    fn payments_action_activated(&self) {
+        self.payments_action();
+    }
+
+    fn cash_summary_action_activated(&self) {
+        self.cash_summary_action();
+    }
+
+    fn stats_action_activated(&self) {
+        self.stats_action();
+    }
+
+    fn sort_by_relevance_action_activated(&self) {
+        self.sort_by_relevance_action();
+    }
+
+    fn print_report_action_activated(&self) {
+        self.print_report_action();
+    }
+
+    fn export_report_action_activated(&self) {
+        self.export_report_action();
+    }
+
     fn toggle_menu_action_activated(&self) {
         let state = !self.togglemenuaction.state();
 
@@ -665,6 +3121,51 @@ impl Window {
         }
     }
 
+    fn fuzzy_search_action_activated(&self) {
+        let state = !self.fuzzysearchaction.state();
+
+        self.fuzzysearchaction.change_state(state);
+        self.search();
+    }
+
+    fn check_in_action_activated(&self) {
+        if self.checkinaction.state() {
+            self.stop_check_in_action();
+        } else {
+            self.start_check_in_action();
+        }
+    }
+
+    fn export_attendance_action_activated(&self) {
+        self.export_attendance_action();
+    }
+
+    // In scan mode the search bar is kept open, ready to receive
+    // codes from a barcode scanner
+    fn scan_mode_action_activated(&self) {
+        let state = !self.scanmodeaction.state();
+
+        if state {
+            self.start_search_action();
+            self.searchentry.set_text("");
+        }
+        self.scanmodeaction.change_state(state);
+    }
+
+    fn sort_column_changed(&self) {
+        let sorted_data: &gtk::TreeModelSort = &*self.sorted_data.borrow();
+
+        match sorted_data.sort_column_id() {
+            Some((gtk::SortColumn::Index(column), order)) => {
+                self.settings.set_sort_order(&*self.source_uri.borrow(), &Column::from(column), order);
+            },
+            Some((gtk::SortColumn::Default, _)) => {
+                self.settings.clear_sort_order(&*self.source_uri.borrow());
+            },
+            None => {},
+        }
+    }
+
     fn menu_popover_closed(&self) {
         self.stop_menu_action();
     }
@@ -677,6 +3178,46 @@ impl Window {
         self.save_action();
     }
 
+    fn copy_rows_action_activated(&self) {
+        self.copy_rows_action();
+    }
+
+    fn paste_rows_action_activated(&self) {
+        self.paste_rows_action();
+    }
+
+    fn copy_header_action_activated(&self) {
+        let state = !self.copyheaderaction.state();
+
+        self.copyheaderaction.change_state(state);
+    }
+
+    fn treeview_key_pressed(&self, event: &gdk::EventKey) -> glib::signal::Inhibit {
+        if !event.state().contains(gdk::ModifierType::CONTROL_MASK) {
+            return glib::signal::Inhibit(false);
+        }
+
+        let keyval = event.keyval().to_lower();
+
+        if keyval == gdk::keys::constants::c {
+            self.copyrowsaction.as_parent().activate(None);
+        } else if keyval == gdk::keys::constants::v {
+            self.pasterowsaction.as_parent().activate(None);
+        } else {
+            return glib::signal::Inhibit(false);
+        }
+
+        glib::signal::Inhibit(true)
+    }
+
+    fn transfer_action_activated(&self) {
+        self.transfer_action();
+    }
+
+    fn convert_to_sqlite_action_activated(&self) {
+        self.convert_to_sqlite_action();
+    }
+
     fn number_cell_edited(&self, path: gtk::TreePath, text: &str) {
         self.update_column(path, &Column::Number, text);
     }
@@ -700,4 +3241,8 @@ impl Window {
     fn delete_event(&self) -> glib::signal::Inhibit {
         self.close_action()
     }
+
+    fn destroyed(&self) {
+        self.application.remove_window(&self.parent);
+    }
 }